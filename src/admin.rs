@@ -0,0 +1,32 @@
+use axum::{
+    extract::{Request, State},
+    http::{header::AUTHORIZATION, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use sha2::{Digest, Sha256};
+
+use crate::state::AppState;
+
+/// Only lets through the requests carrying `Authorization: Bearer <ADMIN_TOKEN>`
+///
+/// The admin endpoints are not found at all when `ADMIN_TOKEN` is not set.
+pub async fn require_admin(State(app): State<AppState>, request: Request, next: Next) -> Response {
+    let Some(admin_token) = &app.config.admin_token else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    let Some(token) = request
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok()?.strip_prefix("Bearer "))
+    else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+    // Digests are compared rather than the tokens, so that the comparison time tells nothing about the token
+    if Sha256::digest(token) != Sha256::digest(admin_token) {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+
+    next.run(request).await
+}
@@ -2,11 +2,11 @@ use leptos::prelude::*;
 use leptos_meta::{provide_meta_context, MetaTags, Stylesheet, Title};
 use leptos_router::{
     components::{Route, Router, Routes},
-    path,
+    path, SsrMode,
 };
 use singlestage::{Theme, ThemeProvider};
 
-use crate::home_page::HomePage;
+use crate::{home_page::HomePage, schedule_page::SchedulePage, streamer_page::StreamerPage};
 
 pub fn shell(options: LeptosOptions) -> impl IntoView {
     view! {
@@ -51,6 +51,9 @@ pub fn App() -> impl IntoView {
                 <main class="max-w-7xl mx-auto py-4">
                     <Routes fallback=|| "Page not found.".into_view()>
                         <Route path=path!("/") view=HomePage />
+                        <Route path=path!("/schedule") view=SchedulePage />
+                        // Rendered once loaded, so that shared links carry the streamer's title and meta tags
+                        <Route path=path!("/s/:channel") view=StreamerPage ssr=SsrMode::Async />
                     </Routes>
                 </main>
             </Router>
@@ -0,0 +1,165 @@
+#[cfg(feature = "ssr")]
+use axum::{
+    extract::Query,
+    http::{
+        header::{CACHE_CONTROL, CONTENT_TYPE, RETRY_AFTER},
+        StatusCode,
+    },
+    response::{IntoResponse, Response},
+};
+#[cfg(feature = "ssr")]
+use chrono::{DateTime, Utc};
+#[cfg(feature = "ssr")]
+use serde::Deserialize;
+
+#[cfg(feature = "ssr")]
+use crate::{
+    fetch_streamers::{Streamer, StreamerResponse},
+    poller,
+    schedule::{coming_up, ScheduledStream},
+};
+
+/// Route of the iCalendar feed of the roster schedules, `?channel=` narrowing it to a single streamer
+pub const CALENDAR_PATH: &str = "/schedule.ics";
+
+/// Calendar apps are asked to refresh the feed this often, matching the schedules cache
+#[cfg(feature = "ssr")]
+const REFRESH_INTERVAL: &str = "PT1H";
+
+/// Longest content line allowed by RFC 5545, in bytes
+#[cfg(feature = "ssr")]
+const MAX_LINE_LENGTH: usize = 75;
+
+#[cfg(feature = "ssr")]
+#[derive(Debug, Deserialize)]
+pub struct CalendarQuery {
+    /// Login of the streamer whose schedule is wanted, everyone's when missing
+    channel: Option<String>,
+}
+
+/// Serves the scheduled streams of the coming week as an RFC 5545 calendar
+///
+/// Calendar apps subscribed to it drop the streams which are no longer listed, such as the canceled ones.
+#[cfg(feature = "ssr")]
+pub async fn calendar(Query(query): Query<CalendarQuery>) -> Response {
+    // The first poll completes a few seconds after startup
+    let Some(response) = poller::snapshot() else {
+        return (StatusCode::SERVICE_UNAVAILABLE, [(RETRY_AFTER, "10")]).into_response();
+    };
+
+    let (name, streamers) = match &query.channel {
+        Some(channel) => {
+            let channel = channel.to_lowercase();
+            let Some(streamer) = response.streamers.iter().find(|s| s.channel_name == channel) else {
+                return StatusCode::NOT_FOUND.into_response();
+            };
+            (
+                format!("{} - WebTV Suspicion", streamer.display_name),
+                std::slice::from_ref(streamer),
+            )
+        }
+        None => ("WebTV Suspicion".to_string(), response.streamers.as_slice()),
+    };
+
+    (
+        [
+            (CONTENT_TYPE, "text/calendar; charset=utf-8"),
+            (CACHE_CONTROL, "public, max-age=300"),
+        ],
+        to_ics(&name, streamers, &response),
+    )
+        .into_response()
+}
+
+#[cfg(feature = "ssr")]
+fn to_ics(name: &str, streamers: &[Streamer], response: &StreamerResponse) -> String {
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//WebTV Suspicion//Schedule//FR".to_string(),
+        "CALSCALE:GREGORIAN".to_string(),
+        "METHOD:PUBLISH".to_string(),
+        // Names and refresh intervals are extensions of RFC 7986, the X- ones being understood by older apps
+        format!("NAME:{}", escape(name)),
+        format!("X-WR-CALNAME:{}", escape(name)),
+        format!("REFRESH-INTERVAL;VALUE=DURATION:{REFRESH_INTERVAL}"),
+        format!("X-PUBLISHED-TTL:{REFRESH_INTERVAL}"),
+    ];
+    for (streamer, stream) in coming_up(streamers) {
+        lines.extend(event(streamer, stream, response));
+    }
+    lines.push("END:VCALENDAR".to_string());
+
+    lines.iter().map(String::as_str).map(fold).collect()
+}
+
+#[cfg(feature = "ssr")]
+fn event(streamer: &Streamer, stream: &ScheduledStream, response: &StreamerResponse) -> Vec<String> {
+    let summary = if stream.title.is_empty() {
+        streamer.display_name.clone()
+    } else {
+        format!("{} - {}", streamer.display_name, stream.title)
+    };
+    let channel_url = format!("https://www.twitch.tv/{}", streamer.channel_name);
+    let mut description = channel_url.clone();
+    if let Some(category) = &stream.category {
+        description = format!("{category}\n{description}");
+    }
+
+    vec![
+        "BEGIN:VEVENT".to_string(),
+        format!("UID:{}@{}", stream.id, response.base_addr),
+        format!("DTSTAMP:{}", date_time(response.fetched_at)),
+        format!("DTSTART:{}", date_time(stream.start_time)),
+        format!("DTEND:{}", date_time(stream.end())),
+        format!("SUMMARY:{}", escape(&summary)),
+        format!("DESCRIPTION:{}", escape(&description)),
+        format!("URL:{channel_url}"),
+        format!("LOCATION:{channel_url}"),
+        "END:VEVENT".to_string(),
+    ]
+}
+
+/// UTC date in the basic format of RFC 5545, as `20260101T180000Z`
+#[cfg(feature = "ssr")]
+fn date_time(date: DateTime<Utc>) -> String {
+    date.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+/// Escapes the characters RFC 5545 gives a meaning to in text values
+#[cfg(feature = "ssr")]
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' | ';' | ',' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {}
+            _ => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+/// Ends a content line with CRLF, splitting it into lines of at most 75 bytes continued by a space
+#[cfg(feature = "ssr")]
+fn fold(line: &str) -> String {
+    let mut folded = String::with_capacity(line.len() + 8);
+    let mut length = 0;
+    for c in line.chars() {
+        if length + c.len_utf8() > MAX_LINE_LENGTH {
+            folded.push_str("\r\n ");
+            // The leading space counts towards the length of the continuation line
+            length = 1;
+        }
+        folded.push(c);
+        length += c.len_utf8();
+    }
+    folded.push_str("\r\n");
+
+    folded
+}
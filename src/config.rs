@@ -0,0 +1,239 @@
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use crate::images::TWITCH_CDN;
+
+/// Settings of the config file, named after their environment variable
+const SETTINGS: [&str; 17] = [
+    "TWITCH_CLIENT_ID",
+    "TWITCH_CLIENT_SECRET",
+    "TWITCH_API_URL",
+    "TWITCH_AUTH_URL",
+    "TWITCH_CDN_URL",
+    "TWITCH_TOKEN_PATH",
+    "TWITCH_PROXY_URL",
+    "TWITCH_CA_CERT",
+    "TWITCH_EVENTSUB_SECRET",
+    "TWITCH_EVENTSUB_CALLBACK",
+    "BASE_ADDR",
+    "ROSTER_PATH",
+    "POLL_INTERVAL_SECS",
+    "SHOW_UNAVAILABLE",
+    "IMAGE_CACHE_DIR",
+    "HISTORY_DB_PATH",
+    "ADMIN_TOKEN",
+];
+
+/// Server settings, loaded once at startup
+///
+/// Every setting is read from, in order of precedence:
+/// - the environment or `.env`, as `TWITCH_CLIENT_ID`
+/// - a file named by the `_FILE` variant, as `TWITCH_CLIENT_ID_FILE=/run/secrets/twitch_client_id`
+/// - the TOML file at `CONFIG_PATH` (`config.toml` when it exists), as `twitch_client_id = "..."`
+#[derive(Clone)]
+pub struct Config {
+    pub twitch_client_id: String,
+    pub twitch_client_secret: String,
+    /// Base URL of the Helix API, overridden to target a mock server
+    pub twitch_api_url: String,
+    /// Base URL of the Twitch OAuth API
+    pub twitch_auth_url: String,
+    /// Base URL of the Twitch CDN the proxied images are fetched from
+    pub twitch_cdn_url: String,
+    /// File the app access token is saved to, so that restarts and redeploys reuse it
+    pub twitch_token_path: Option<PathBuf>,
+    pub twitch_proxy_url: Option<String>,
+    /// PEM bundle of root certificates trusted in addition to the system ones
+    pub twitch_ca_cert: Option<PathBuf>,
+    /// EventSub is enabled when both its secret and callback are set
+    pub eventsub: Option<EventSubConfig>,
+    /// Domain the page is served from, which the Twitch embed requires
+    pub base_addr: String,
+    /// Roster file, watched for modifications
+    pub roster_path: PathBuf,
+    /// Interval between two polls of Twitch
+    pub poll_interval: Duration,
+    /// Whether roster members Twitch could not resolve are shown as greyed cards
+    pub show_unavailable: bool,
+    /// Directory the Twitch images served to visitors are cached in
+    pub image_cache_dir: PathBuf,
+    /// SQLite database the stream sessions are recorded in
+    #[cfg(feature = "history")]
+    pub history_db_path: PathBuf,
+    /// Bearer token required by the admin endpoints, which are disabled without it
+    pub admin_token: Option<String>,
+}
+
+#[derive(Clone)]
+pub struct EventSubConfig {
+    pub secret: String,
+    /// Public HTTPS URL of the webhook, Twitch only delivers to port 443
+    pub callback: String,
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(PathBuf, std::io::Error),
+    Parse(PathBuf, String),
+    UnknownSetting(PathBuf, String),
+    Missing(&'static str),
+    Invalid { name: &'static str, reason: String },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(path, e) => write!(f, "could not read {}: {e}", path.display()),
+            Self::Parse(path, e) => write!(f, "could not parse config file {}: {e}", path.display()),
+            Self::UnknownSetting(path, key) => write!(f, "unknown setting {key:?} in config file {}", path.display()),
+            Self::Missing(name) => write!(
+                f,
+                "missing setting {name} (set {name}, {name}_FILE or {} in the config file)",
+                name.to_lowercase()
+            ),
+            Self::Invalid { name, reason } => write!(f, "invalid setting {name}: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl Config {
+    pub fn load() -> Result<Self, ConfigError> {
+        let sources = Sources::load()?;
+
+        let eventsub = match (
+            sources.get("TWITCH_EVENTSUB_SECRET")?,
+            sources.get("TWITCH_EVENTSUB_CALLBACK")?,
+        ) {
+            (None, None) => None,
+            (Some(_), None) => return Err(ConfigError::Missing("TWITCH_EVENTSUB_CALLBACK")),
+            (None, Some(_)) => return Err(ConfigError::Missing("TWITCH_EVENTSUB_SECRET")),
+            (Some(secret), Some(callback)) => {
+                // Twitch refuses secrets outside of this range
+                if !(10..=100).contains(&secret.len()) {
+                    return Err(ConfigError::Invalid {
+                        name: "TWITCH_EVENTSUB_SECRET",
+                        reason: "expected 10 to 100 characters".to_string(),
+                    });
+                }
+                if !callback.starts_with("https://") {
+                    return Err(ConfigError::Invalid {
+                        name: "TWITCH_EVENTSUB_CALLBACK",
+                        reason: format!("{callback:?} is not an HTTPS URL"),
+                    });
+                }
+                Some(EventSubConfig { secret, callback })
+            }
+        };
+
+        let poll_interval_secs = match sources.get("POLL_INTERVAL_SECS")? {
+            Some(value) => value
+                .parse::<u64>()
+                .ok()
+                .filter(|secs| *secs > 0)
+                .ok_or_else(|| ConfigError::Invalid {
+                    name: "POLL_INTERVAL_SECS",
+                    reason: format!("{value:?} is not a positive number of seconds"),
+                })?,
+            None => 60,
+        };
+
+        let show_unavailable = match sources.get("SHOW_UNAVAILABLE")?.as_deref() {
+            Some("true" | "1") => true,
+            Some("false" | "0") | None => false,
+            Some(value) => {
+                return Err(ConfigError::Invalid {
+                    name: "SHOW_UNAVAILABLE",
+                    reason: format!("{value:?} is not a boolean"),
+                })
+            }
+        };
+
+        Ok(Self {
+            twitch_client_id: sources.required("TWITCH_CLIENT_ID")?,
+            twitch_client_secret: sources.required("TWITCH_CLIENT_SECRET")?,
+            twitch_api_url: sources
+                .get("TWITCH_API_URL")?
+                .unwrap_or_else(|| "https://api.twitch.tv/helix".to_string()),
+            twitch_auth_url: sources
+                .get("TWITCH_AUTH_URL")?
+                .unwrap_or_else(|| "https://id.twitch.tv/oauth2".to_string()),
+            twitch_cdn_url: sources.get("TWITCH_CDN_URL")?.unwrap_or_else(|| TWITCH_CDN.to_string()),
+            twitch_token_path: sources.get("TWITCH_TOKEN_PATH")?.map(PathBuf::from),
+            twitch_proxy_url: sources.get("TWITCH_PROXY_URL")?,
+            twitch_ca_cert: sources.get("TWITCH_CA_CERT")?.map(PathBuf::from),
+            eventsub,
+            base_addr: sources.get("BASE_ADDR")?.unwrap_or_else(|| "127.0.0.1".to_string()),
+            roster_path: sources
+                .get("ROSTER_PATH")?
+                .map_or_else(|| PathBuf::from("roster.toml"), PathBuf::from),
+            poll_interval: Duration::from_secs(poll_interval_secs),
+            show_unavailable,
+            image_cache_dir: sources
+                .get("IMAGE_CACHE_DIR")?
+                .map_or_else(|| std::env::temp_dir().join("webtv-images"), PathBuf::from),
+            #[cfg(feature = "history")]
+            history_db_path: sources
+                .get("HISTORY_DB_PATH")?
+                .map_or_else(|| PathBuf::from("history.db"), PathBuf::from),
+            // An empty token, as left by `ADMIN_TOKEN=`, would let in requests without credentials
+            admin_token: sources.get("ADMIN_TOKEN")?.filter(|token| !token.is_empty()),
+        })
+    }
+}
+
+struct Sources {
+    file: toml::Table,
+}
+
+impl Sources {
+    fn load() -> Result<Self, ConfigError> {
+        let path = match dotenvy::var("CONFIG_PATH") {
+            Ok(path) => PathBuf::from(path),
+            Err(_) if Path::new("config.toml").exists() => PathBuf::from("config.toml"),
+            Err(_) => {
+                return Ok(Self {
+                    file: toml::Table::new(),
+                })
+            }
+        };
+
+        let content = std::fs::read_to_string(&path).map_err(|e| ConfigError::Io(path.clone(), e))?;
+        let file =
+            toml::from_str::<toml::Table>(&content).map_err(|e| ConfigError::Parse(path.clone(), e.to_string()))?;
+        if let Some(key) = file
+            .keys()
+            .find(|key| !SETTINGS.iter().any(|name| name.to_lowercase() == **key))
+        {
+            return Err(ConfigError::UnknownSetting(path, key.clone()));
+        }
+
+        Ok(Self { file })
+    }
+
+    fn get(&self, name: &'static str) -> Result<Option<String>, ConfigError> {
+        if let Ok(value) = dotenvy::var(name) {
+            return Ok(Some(value));
+        }
+        if let Ok(path) = dotenvy::var(format!("{name}_FILE")) {
+            // Secrets files usually end with a newline
+            let value = std::fs::read_to_string(&path).map_err(|e| ConfigError::Io(path.into(), e))?;
+            return Ok(Some(value.trim_end().to_string()));
+        }
+
+        Ok(self.file.get(&name.to_lowercase()).map(|value| match value {
+            toml::Value::String(s) => s.clone(),
+            value => value.to_string(),
+        }))
+    }
+
+    fn required(&self, name: &'static str) -> Result<String, ConfigError> {
+        self.get(name)?
+            .filter(|value| !value.is_empty())
+            .ok_or(ConfigError::Missing(name))
+    }
+}
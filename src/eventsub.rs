@@ -0,0 +1,267 @@
+use axum::{
+    body::Bytes,
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, TimeDelta, Utc};
+use hmac::{Hmac, Mac};
+use leptos::logging::{error, log, warn};
+use serde::Deserialize;
+use serde_json::json;
+use sha2::Sha256;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{LazyLock, Mutex},
+    time::Duration,
+};
+
+use crate::{
+    config::EventSubConfig, fetch_streamers::fetch_user_ids, helix, poller, state::AppState,
+    twitch_client::TwitchClient, twitch_error::TwitchError,
+};
+
+/// Route receiving EventSub webhook notifications
+pub const EVENTSUB_PATH: &str = "/eventsub";
+
+const SUBSCRIPTION_TYPES: [&str; 2] = ["stream.online", "stream.offline"];
+
+/// Older messages are rejected, as recommended by Twitch to prevent replay attacks
+const MAX_MESSAGE_AGE: TimeDelta = TimeDelta::minutes(10);
+
+/// Helix takes a moment to return the title and viewer count of a stream that just went live
+const ONLINE_REFRESH_DELAY: Duration = Duration::from_secs(30);
+
+/// IDs of recently handled messages, since Twitch may deliver a message more than once
+static SEEN_MESSAGES: LazyLock<Mutex<HashMap<String, DateTime<Utc>>>> = LazyLock::new(Default::default);
+
+type HmacSha256 = Hmac<Sha256>;
+
+#[derive(Debug, Deserialize)]
+struct Message {
+    subscription: Subscription,
+    challenge: Option<String>,
+    event: Option<StreamEvent>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Subscription {
+    id: String,
+    #[serde(rename = "type")]
+    kind: String,
+    status: String,
+    condition: Condition,
+    transport: Transport,
+}
+
+#[derive(Debug, Deserialize)]
+struct Condition {
+    broadcaster_user_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Transport {
+    callback: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct StreamEvent {
+    broadcaster_user_id: String,
+}
+
+/// Value of the `Twitch-Eventsub-Message-Signature` header for a message
+pub fn signature(secret: &str, message_id: &str, timestamp: &str, body: &[u8]) -> String {
+    format!(
+        "sha256={}",
+        hex::encode(mac(secret, message_id, timestamp, body).finalize().into_bytes())
+    )
+}
+
+fn mac(secret: &str, message_id: &str, timestamp: &str, body: &[u8]) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(message_id.as_bytes());
+    mac.update(timestamp.as_bytes());
+    mac.update(body);
+    mac
+}
+
+fn has_valid_signature(secret: &str, message_id: &str, timestamp: &str, body: &[u8], signature: &str) -> bool {
+    signature
+        .strip_prefix("sha256=")
+        .and_then(|s| hex::decode(s).ok())
+        // Constant time comparison
+        .is_some_and(|s| mac(secret, message_id, timestamp, body).verify_slice(&s).is_ok())
+}
+
+fn is_recent(timestamp: &str) -> bool {
+    DateTime::parse_from_rfc3339(timestamp).is_ok_and(|t| Utc::now() - t.with_timezone(&Utc) < MAX_MESSAGE_AGE)
+}
+
+/// Records the message and returns whether it was not handled before
+fn is_first_delivery(message_id: &str) -> bool {
+    let now = Utc::now();
+    let mut seen = SEEN_MESSAGES.lock().expect("Seen messages lock is not poisoned");
+    // Older messages are rejected anyway
+    seen.retain(|_, received_at| now - *received_at < MAX_MESSAGE_AGE);
+
+    seen.insert(message_id.to_string(), now).is_none()
+}
+
+fn header<'a>(headers: &'a HeaderMap, name: &str) -> &'a str {
+    headers.get(name).and_then(|v| v.to_str().ok()).unwrap_or_default()
+}
+
+/// Handles the challenge handshake, notifications and revocations sent by Twitch
+pub async fn eventsub_webhook(State(app): State<AppState>, headers: HeaderMap, body: Bytes) -> Response {
+    let Some(config) = &app.config.eventsub else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    let message_id = header(&headers, "Twitch-Eventsub-Message-Id");
+    let timestamp = header(&headers, "Twitch-Eventsub-Message-Timestamp");
+    let signature = header(&headers, "Twitch-Eventsub-Message-Signature");
+    if !has_valid_signature(&config.secret, message_id, timestamp, &body, signature) {
+        warn!("Rejected EventSub message {message_id:?} with an invalid signature");
+        return StatusCode::FORBIDDEN.into_response();
+    }
+    if !is_recent(timestamp) {
+        warn!("Rejected EventSub message {message_id:?} sent at {timestamp:?}");
+        return StatusCode::FORBIDDEN.into_response();
+    }
+
+    let Ok(message) = serde_json::from_slice::<Message>(&body) else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+
+    match header(&headers, "Twitch-Eventsub-Message-Type") {
+        "webhook_callback_verification" => {
+            log!("verified EventSub subscription to {}", message.subscription.kind);
+            message.challenge.unwrap_or_default().into_response()
+        }
+        "notification" => {
+            if is_first_delivery(message_id) {
+                handle_notification(message);
+            }
+            StatusCode::NO_CONTENT.into_response()
+        }
+        "revocation" => {
+            warn!(
+                "Twitch revoked EventSub subscription to {} for broadcaster {:?}: {}, subscribing again and polling meanwhile",
+                message.subscription.kind,
+                message.subscription.condition.broadcaster_user_id.unwrap_or_default(),
+                message.subscription.status,
+            );
+            poller::request_refresh();
+            spawn_sync(app.clone());
+            StatusCode::NO_CONTENT.into_response()
+        }
+        _ => StatusCode::NO_CONTENT.into_response(),
+    }
+}
+
+fn handle_notification(message: Message) {
+    let Some(event) = message.event else {
+        return;
+    };
+
+    match message.subscription.kind.as_str() {
+        "stream.online" => {
+            poller::set_live(&event.broadcaster_user_id, true);
+            tokio::spawn(async {
+                tokio::time::sleep(ONLINE_REFRESH_DELAY).await;
+                poller::request_refresh();
+            });
+        }
+        "stream.offline" => poller::set_live(&event.broadcaster_user_id, false),
+        _ => {}
+    }
+}
+
+/// Subscribes to stream events of every roster member, and removes our subscriptions that are no longer needed
+pub async fn sync_subscriptions(app: &AppState) -> Result<(), TwitchError> {
+    let Some(config) = &app.config.eventsub else {
+        return Ok(());
+    };
+    let client = &app.twitch;
+    let roster = crate::roster::get().ok_or_else(|| TwitchError::Server("Roster not loaded".to_string()))?;
+
+    let user_ids = fetch_user_ids(client, &roster.streamers).await?;
+    let mut missing = user_ids
+        .values()
+        .flat_map(|id| SUBSCRIPTION_TYPES.map(|kind| (id.clone(), kind.to_string())))
+        .collect::<HashSet<_>>();
+
+    let subscriptions = helix::get_pages::<Subscription>(client, "eventsub/subscriptions", &[], true).await?;
+    for subscription in subscriptions
+        .into_iter()
+        .filter(|s| s.transport.callback.as_deref() == Some(config.callback.as_str()))
+    {
+        let is_healthy = matches!(
+            subscription.status.as_str(),
+            "enabled" | "webhook_callback_verification_pending"
+        );
+        let key = (
+            subscription.condition.broadcaster_user_id.unwrap_or_default(),
+            subscription.kind,
+        );
+
+        // Broadcasters who left the roster, failing subscriptions and duplicates
+        if !is_healthy || !missing.remove(&key) {
+            delete_subscription(client, &subscription.id).await?;
+        }
+    }
+
+    for (broadcaster_user_id, kind) in &missing {
+        create_subscription(client, config, broadcaster_user_id, kind).await?;
+    }
+    if !missing.is_empty() {
+        log!("created {} EventSub subscriptions", missing.len());
+    }
+
+    Ok(())
+}
+
+async fn create_subscription(
+    client: &TwitchClient,
+    config: &EventSubConfig,
+    broadcaster_user_id: &str,
+    kind: &str,
+) -> Result<(), TwitchError> {
+    let request = client
+        .post(format!("{}/eventsub/subscriptions", client.api_url()))
+        .json(&json!({
+            "type": kind,
+            "version": "1",
+            "condition": { "broadcaster_user_id": broadcaster_user_id },
+            "transport": {
+                "method": "webhook",
+                "callback": config.callback,
+                "secret": config.secret,
+            },
+        }));
+    client.send(request).await?;
+
+    Ok(())
+}
+
+async fn delete_subscription(client: &TwitchClient, id: &str) -> Result<(), TwitchError> {
+    let request = client
+        .delete(format!("{}/eventsub/subscriptions", client.api_url()))
+        .query(&[("id", id)]);
+    client.send(request).await?;
+
+    Ok(())
+}
+
+/// Syncs subscriptions in the background, if EventSub is enabled
+pub fn spawn_sync(app: AppState) {
+    if app.config.eventsub.is_none() {
+        return;
+    }
+
+    tokio::spawn(async move {
+        if let Err(e) = sync_subscriptions(&app).await {
+            error!("Could not sync EventSub subscriptions: {e}");
+        }
+    });
+}
@@ -0,0 +1,51 @@
+#[cfg(feature = "history")]
+use chrono::{TimeDelta, Utc};
+use leptos::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{fetch_streamers::Streamer, history::StreamSession, twitch_error::TwitchError};
+
+/// Sessions shown on the streamer pages, and used for their usual streaming hours
+#[cfg(feature = "history")]
+const HISTORY_DAYS: i64 = 30;
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct StreamerDetails {
+    pub base_addr: String,
+    pub streamer: Streamer,
+    /// Twitch bio, empty when the streamer wrote none
+    pub description: String,
+    /// Sessions of the last 30 days, the most recent first, `None` when the history is disabled
+    pub sessions: Option<Vec<StreamSession>>,
+}
+
+/// Details of the roster member streaming on `channel`, `None` when nobody in the roster does
+#[server(GetStreamerDetails)]
+pub async fn fetch_streamer_details(channel: String) -> Result<Option<StreamerDetails>, TwitchError> {
+    let response = crate::fetch_streamers::fetch_streamers().await?;
+    let channel = channel.to_lowercase();
+    let Some(streamer) = response.streamers.into_iter().find(|s| s.channel_name == channel) else {
+        return Ok(None);
+    };
+
+    let description = crate::fetch_streamers::cached_description(&streamer.user_id)
+        .await
+        .unwrap_or_default();
+
+    #[cfg(feature = "history")]
+    let sessions = expect_context::<crate::state::AppState>()
+        .history
+        .sessions_since(&streamer.user_id, Utc::now() - TimeDelta::days(HISTORY_DAYS))
+        .await
+        .inspect_err(|e| leptos::logging::error!("Could not read the sessions of {channel}: {e}"))
+        .ok();
+    #[cfg(not(feature = "history"))]
+    let sessions = None;
+
+    Ok(Some(StreamerDetails {
+        base_addr: response.base_addr,
+        streamer,
+        description,
+        sessions,
+    }))
+}
@@ -1,187 +1,370 @@
-#[cfg(feature = "ssr")]
-use cached::proc_macro::cached;
+use chrono::{DateTime, Utc};
 use leptos::prelude::*;
-#[cfg(feature = "ssr")]
-use reqwest::{header, Client};
 use serde::{Deserialize, Serialize};
-use std::time::Duration;
 #[cfg(feature = "ssr")]
-use std::{cmp::Reverse, collections::HashMap};
+use std::{cmp::Reverse, collections::HashMap, sync::LazyLock};
+use std::{fmt, time::Duration};
+#[cfg(feature = "ssr")]
+use tokio::sync::Mutex;
 
 #[cfg(feature = "ssr")]
-use crate::get_credentials::get_access_token;
+use crate::{
+    helix, login_cache::LoginCache, poller, reruns, roster::RosterEntry, schedule, state::AppState,
+    twitch_client::TwitchClient,
+};
+use crate::{schedule::ScheduledStream, twitch_error::TwitchError};
 
-struct FetchStreamer(String, String);
+#[cfg(feature = "ssr")]
+static USERS_CACHE: LazyLock<Mutex<UsersCache>> = LazyLock::new(|| {
+    Mutex::new(UsersCache {
+        users: LoginCache::new(Duration::from_secs(36000)),
+        unknown_logins: LoginCache::new(Duration::from_secs(36000)),
+    })
+});
 
 #[cfg(feature = "ssr")]
-#[derive(Debug, Deserialize)]
-struct TwitchUsersResponse {
-    data: Vec<StreamerUserData>,
+struct UsersCache {
+    /// Keyed by user ID
+    users: LoginCache<StreamerUserData>,
+    /// Roster logins Twitch does not know, so that they are not resolved on every poll
+    unknown_logins: LoginCache<()>,
 }
 
 #[derive(Debug, Deserialize, Clone)]
 struct StreamerUserData {
+    id: String,
     login: String,
     profile_image_url: String,
-}
-
-#[cfg(feature = "ssr")]
-#[derive(Debug, Deserialize)]
-struct TwitchStreamsResponse {
-    data: Vec<StreamerStreamData>,
+    /// Empty when the streamer has no offline banner
+    offline_image_url: String,
+    /// Bio, empty when the streamer wrote none
+    description: String,
 }
 
 #[derive(Debug, Deserialize, Clone)]
 struct StreamerStreamData {
-    user_login: String,
+    user_id: String,
     title: String,
     viewer_count: u32,
+    game_name: String,
+    started_at: DateTime<Utc>,
+    language: String,
+    /// `null` when the stream has no tags
+    #[serde(default)]
+    tags: Option<Vec<String>>,
+    is_mature: bool,
+    /// Contains `{width}` and `{height}` placeholders
+    thumbnail_url: String,
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
 pub struct Streamer {
+    /// Twitch user ID
+    pub user_id: String,
     pub display_name: String,
+    /// Current login, which differs from the roster one when the streamer renamed their account
     pub channel_name: String,
     pub avatar_url: String,
     pub is_live: bool,
     pub viewer_count: Option<u32>,
     pub stream_title: Option<String>,
+    /// Category, as "Just Chatting"
+    pub game_name: Option<String>,
+    pub started_at: Option<DateTime<Utc>>,
+    /// ISO 639-1 code, as "fr"
+    pub language: Option<String>,
+    pub tags: Vec<String>,
+    pub is_mature: bool,
+    /// Preview of the live stream, changing every few minutes
+    pub thumbnail_url: Option<String>,
+    /// Banner shown by Twitch while the channel is offline
+    pub offline_image_url: Option<String>,
+    /// Streams planned for the coming week, the next one first
+    pub schedule: Vec<ScheduledStream>,
 }
 
+/// Size of the stream previews, as shown by the cards
+#[cfg(feature = "ssr")]
+const THUMBNAIL_WIDTH: &str = "854";
+#[cfg(feature = "ssr")]
+const THUMBNAIL_HEIGHT: &str = "480";
+
+/// Twitch renews stream previews every 5 minutes, but serves them with long cache lifetimes
+#[cfg(feature = "ssr")]
+const THUMBNAIL_REFRESH_SECS: i64 = 300;
+
+#[cfg(feature = "ssr")]
 impl Streamer {
-    fn from(fetch: FetchStreamer, user: StreamerUserData, stream: Option<StreamerStreamData>) -> Self {
+    fn from(
+        entry: &RosterEntry,
+        user: StreamerUserData,
+        stream: Option<StreamerStreamData>,
+        schedule: &[ScheduledStream],
+    ) -> Self {
+        let now = Utc::now();
+        // Changes with every renewal of the previews, so that browsers fetch the new one
+        let cache_buster = now.timestamp() / THUMBNAIL_REFRESH_SECS;
+
         Self {
-            display_name: fetch.0,
-            channel_name: fetch.1,
+            user_id: user.id,
+            display_name: entry.display_name.clone(),
+            channel_name: user.login.to_lowercase(),
             avatar_url: user.profile_image_url,
             is_live: stream.is_some(),
             viewer_count: stream.as_ref().map(|s| s.viewer_count),
+            game_name: stream.as_ref().map(|s| s.game_name.clone()).filter(|g| !g.is_empty()),
+            started_at: stream.as_ref().map(|s| s.started_at),
+            language: stream.as_ref().map(|s| s.language.clone()).filter(|l| !l.is_empty()),
+            is_mature: stream.as_ref().is_some_and(|s| s.is_mature),
+            thumbnail_url: stream.as_ref().map(|s| {
+                let url = s
+                    .thumbnail_url
+                    .replace("{width}", THUMBNAIL_WIDTH)
+                    .replace("{height}", THUMBNAIL_HEIGHT);
+                format!("{url}?t={cache_buster}")
+            }),
+            offline_image_url: Some(user.offline_image_url).filter(|url| !url.is_empty()),
+            schedule: schedule::upcoming(schedule, now),
+            tags: stream.as_ref().and_then(|s| s.tags.clone()).unwrap_or_default(),
             stream_title: stream.map(|s| s.title),
         }
     }
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+/// Roster member Twitch could not resolve
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct UnavailableStreamer {
+    pub display_name: String,
+    /// Login from the roster
+    pub login: String,
+    pub reason: UnavailableReason,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
+pub enum UnavailableReason {
+    /// No Twitch account has this login, usually a typo in the roster
+    UnknownLogin,
+    /// The account was found before, it has since been suspended, banned or deleted
+    AccountGone,
+}
+
+impl UnavailableReason {
+    /// Explanation shown to visitors
+    pub fn user_message(&self) -> &'static str {
+        match self {
+            Self::UnknownLogin => "Chaîne introuvable",
+            Self::AccountGone => "Chaîne suspendue ou supprimée",
+        }
+    }
+}
+
+impl fmt::Display for UnavailableReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownLogin => write!(f, "no Twitch account has this login"),
+            Self::AccountGone => write!(f, "the Twitch account was suspended, banned or deleted"),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
 pub struct StreamerResponse {
     pub base_addr: String,
     pub streamers: Vec<Streamer>,
+    /// Roster members missing from `streamers`
+    pub unavailable: Vec<UnavailableStreamer>,
+    /// Whether the page shows `unavailable` as greyed cards, set by `SHOW_UNAVAILABLE`
+    pub show_unavailable: bool,
+    /// When Twitch was last queried successfully
+    pub fetched_at: DateTime<Utc>,
+    /// Whether the latest query failed, the streamers being the last known good ones
+    pub stale: bool,
+}
+
+impl StreamerResponse {
+    /// Whether both responses show the same streamers, regardless of when they were fetched
+    pub fn same_data(&self, other: &Self) -> bool {
+        Self {
+            fetched_at: other.fetched_at,
+            ..self.clone()
+        } == *other
+    }
 }
 
 #[cfg(feature = "ssr")]
-#[cached(
-    time = 36000,
-    result = true,
-    sync_writes = "default",
-    key = "String",
-    convert = r#"{ "users".to_string() }"#
-)]
 async fn fetch_users_data(
-    client: &Client,
-    streamers_to_fetch: &[FetchStreamer],
-) -> Result<HashMap<String, StreamerUserData>, ServerFnError> {
-    let request_params = streamers_to_fetch
+    client: &TwitchClient,
+    streamers_to_fetch: &[RosterEntry],
+) -> Result<HashMap<String, StreamerUserData>, TwitchError> {
+    use leptos::logging::warn;
+
+    // Held during the requests, so that the poller and the EventSub subscription sync resolve each user once
+    let mut cache = USERS_CACHE.lock().await;
+    let cache = &mut *cache;
+
+    // Streamers are looked up by ID once resolved, so that renamed accounts are still found
+    let entries_by_id = streamers_to_fetch
         .iter()
-        .map(|s| format!("login={}", s.1))
-        .collect::<Vec<_>>()
-        .join("&");
+        .filter_map(|s| Some((s.id.clone()?, s)))
+        .collect::<HashMap<_, _>>();
+    let (mut users, missing_ids) = cache.users.lookup(entries_by_id.keys().map(String::as_str));
+    let (_, unresolved_logins) = cache.unknown_logins.lookup(
+        streamers_to_fetch
+            .iter()
+            .filter(|s| s.id.is_none())
+            .map(|s| s.login.as_str()),
+    );
 
-    let users_response = client
-        .get(format!("https://api.twitch.tv/helix/users?{}", request_params))
-        .send()
-        .await?
-        .json::<TwitchUsersResponse>()
-        .await?;
+    let (fetched, resolved) = tokio::try_join!(
+        helix::get_all::<StreamerUserData>(client, "users", "id", &missing_ids, false),
+        helix::get_all::<StreamerUserData>(client, "users", "login", &unresolved_logins, false),
+    )?;
+
+    let mut fetched = fetched
+        .into_iter()
+        .map(|u| (u.id.clone(), u))
+        .collect::<HashMap<_, _>>();
+    for id in missing_ids {
+        let user = fetched.remove(&id);
+        if let Some(user) = &user {
+            let entry = entries_by_id[&id];
+            if user.login != entry.login {
+                warn!(
+                    "{} renamed their Twitch account from {} to {}, update the roster",
+                    entry.display_name, entry.login, user.login
+                );
+            }
+            users.insert(id.clone(), user.clone());
+        }
+        cache.users.insert(id, user);
+    }
 
-    Ok(users_response
-        .data
+    let mut resolved = resolved
         .into_iter()
-        .map(|u| (u.login.clone().to_lowercase(), u))
-        .collect::<HashMap<_, _>>())
+        .map(|u| (u.login.to_lowercase(), u))
+        .collect::<HashMap<_, _>>();
+    let mut resolved_ids = HashMap::new();
+    for login in unresolved_logins {
+        match resolved.remove(&login) {
+            Some(user) => {
+                resolved_ids.insert(login, user.id.clone());
+                users.insert(user.id.clone(), user.clone());
+                cache.users.insert(user.id.clone(), Some(user));
+            }
+            None => cache.unknown_logins.insert(login, None),
+        }
+    }
+    if !resolved_ids.is_empty() {
+        crate::roster::record_user_ids(&resolved_ids);
+    }
+
+    Ok(streamers_to_fetch
+        .iter()
+        .filter_map(|s| {
+            let id = s.id.as_ref().or_else(|| resolved_ids.get(&s.login))?;
+            Some((s.login.clone(), users.get(id)?.clone()))
+        })
+        .collect())
 }
 
 #[cfg(feature = "ssr")]
-#[cached(
-    time = 300,
-    result = true,
-    sync_writes = "default",
-    key = "String",
-    convert = r#"{ "streams".to_string() }"#
-)]
 async fn fetch_streams_data(
-    client: &Client,
-    streamers_to_fetch: &[FetchStreamer],
-) -> Result<HashMap<String, StreamerStreamData>, ServerFnError> {
-    let request_params = streamers_to_fetch
-        .iter()
-        .map(|s| format!("user_login={}", s.1))
-        .collect::<Vec<_>>()
-        .join("&");
+    client: &TwitchClient,
+    user_ids: &[String],
+) -> Result<HashMap<String, StreamerStreamData>, TwitchError> {
+    Ok(
+        helix::get_all::<StreamerStreamData>(client, "streams", "user_id", user_ids, true)
+            .await?
+            .into_iter()
+            .map(|s| (s.user_id.clone(), s))
+            .collect::<HashMap<_, _>>(),
+    )
+}
 
-    let streams_response = client
-        .get(format!("https://api.twitch.tv/helix/streams?{}", request_params))
-        .send()
-        .await?
-        .json::<TwitchStreamsResponse>()
-        .await?;
+/// Drops cached Twitch data so the next request reflects the current roster
+#[cfg(feature = "ssr")]
+pub async fn clear_caches() {
+    let mut cache = USERS_CACHE.lock().await;
+    cache.users.clear();
+    cache.unknown_logins.clear();
+    schedule::clear_cache().await;
+    reruns::clear_cache().await;
+    poller::clear_overrides();
+}
 
-    Ok(streams_response
-        .data
-        .into_iter()
-        .map(|s| (s.user_login.clone(), s))
-        .collect::<HashMap<_, _>>())
+/// Twitch bio of a roster member, as fetched by the last polls
+#[cfg(feature = "ssr")]
+pub async fn cached_description(user_id: &str) -> Option<String> {
+    let (mut users, _) = USERS_CACHE.lock().await.users.lookup([user_id]);
+
+    users.remove(user_id).map(|user| user.description)
 }
 
+/// Latest roster data, as polled in the background
+///
+/// When the last poll failed, the last known good data is returned flagged as stale.
 #[server(GetStreamers)]
-pub async fn fetch_streamers() -> Result<StreamerResponse, ServerFnError> {
-    use axum::http::{HeaderMap, HeaderValue};
+pub async fn fetch_streamers() -> Result<StreamerResponse, TwitchError> {
+    match crate::poller::snapshot() {
+        Some(response) => Ok(response),
+        // First poll still in progress
+        None => crate::poller::refresh(&expect_context::<AppState>())
+            .await
+            .inspect_err(|e| leptos::logging::error!("Could not load streamers: {e}")),
+    }
+}
 
+/// Queries Twitch for the current state of every roster member
+#[cfg(feature = "ssr")]
+pub async fn load_streamers(app: &AppState) -> Result<StreamerResponse, TwitchError> {
     // Streamers to fetch
-    let streamers_to_fetch = vec![
-        FetchStreamer("Shokk".to_string(), "shokkfamedslayer".to_string()),
-        FetchStreamer("Cuzdot".to_string(), "cuzdot".to_string()),
-        FetchStreamer("Eden".to_string(), "edenwod".to_string()),
-        FetchStreamer("Taco".to_string(), "tacokek".to_string()),
-        FetchStreamer("TT".to_string(), "t_t_27".to_string()),
-        FetchStreamer("Turbo".to_string(), "Turbogronil".to_string()),
-        FetchStreamer("Anda".to_string(), "Andazara".to_string()),
-        FetchStreamer("Tinky".to_string(), "tinky_lol".to_string()),
-        FetchStreamer("Vaelin".to_string(), "vaelinhc".to_string()),
-        FetchStreamer("Dife".to_string(), "zilakin".to_string()),
-        FetchStreamer("Cruzz Croix V".to_string(), "cruzzxv".to_string()),
-        FetchStreamer("Spanra".to_string(), "spannra".to_string()),
-    ];
-
-    // Credentials
-    let client_id = dotenvy::var("TWITCH_CLIENT_ID").map_err(|_| ServerFnError::new("Missing TWITCH_CLIENT_ID"))?;
-    let access_token = get_access_token().await?;
-
-    // Query Twitch
-    let mut headers = HeaderMap::new();
-    headers.insert("Client-ID", HeaderValue::from_str(&client_id)?);
-    headers.insert(
-        header::AUTHORIZATION,
-        HeaderValue::from_str(&format!("Bearer {}", &access_token))?,
-    );
-    let client = reqwest::Client::builder().default_headers(headers).build()?;
+    let roster = crate::roster::get().ok_or_else(|| TwitchError::Server("Roster not loaded".to_string()))?;
+    let streamers_to_fetch = &roster.streamers;
 
-    let res = tokio::try_join!(
-        fetch_users_data(&client, &streamers_to_fetch),
-        fetch_streams_data(&client, &streamers_to_fetch)
-    );
-    let (mut users_map, mut streams_map) = res?;
+    // Query Twitch, streams being looked up by the user IDs
+    let mut users_map = fetch_users_data(&app.twitch, streamers_to_fetch).await?;
+    let user_ids = users_map.values().map(|u| u.id.clone()).collect::<Vec<_>>();
+    let mut streams_map = fetch_streams_data(&app.twitch, &user_ids).await?;
+    let schedules = schedule::fetch_schedules(&app.twitch, &user_ids).await;
 
+    let mut unavailable = Vec::new();
     let mut streamers = streamers_to_fetch
-        .into_iter()
+        .iter()
         .filter_map(|s| {
-            let user = users_map.remove(&s.1.to_lowercase())?;
-            let stream = streams_map.remove(&s.1.to_lowercase());
+            let Some(user) = users_map.remove(&s.login) else {
+                unavailable.push(UnavailableStreamer {
+                    display_name: s.display_name.clone(),
+                    login: s.login.clone(),
+                    // Logins are only resolved once
+                    reason: if s.id.is_some() {
+                        UnavailableReason::AccountGone
+                    } else {
+                        UnavailableReason::UnknownLogin
+                    },
+                });
+                return None;
+            };
+            let stream = streams_map.remove(&user.id);
+            let schedule = schedules.get(&user.id).map(Vec::as_slice).unwrap_or_default();
 
-            Some(Streamer::from(s, user, stream))
+            Some(Streamer::from(s, user, stream, schedule))
         })
         .collect::<Vec<_>>();
 
-    let base_addr = dotenvy::var("BASE_ADDR").unwrap_or_else(|_| "127.0.0.1".to_string());
+    sort_streamers(&mut streamers);
+    Ok(StreamerResponse {
+        base_addr: app.config.base_addr.clone(),
+        streamers,
+        unavailable,
+        show_unavailable: app.config.show_unavailable,
+        fetched_at: Utc::now(),
+        stale: false,
+    })
+}
 
+/// Live streamers first by viewer count, then everyone else by name
+#[cfg(feature = "ssr")]
+pub(crate) fn sort_streamers(streamers: &mut [Streamer]) {
     streamers.sort_by_key(|s| {
         (
             Reverse(s.is_live),
@@ -189,5 +372,17 @@ pub async fn fetch_streamers() -> Result<StreamerResponse, ServerFnError> {
             s.display_name.to_lowercase(),
         )
     });
-    Ok(StreamerResponse { base_addr, streamers })
+}
+
+/// Twitch user IDs of roster members, keyed by login
+#[cfg(feature = "ssr")]
+pub async fn fetch_user_ids(
+    client: &TwitchClient,
+    streamers: &[RosterEntry],
+) -> Result<HashMap<String, String>, TwitchError> {
+    Ok(fetch_users_data(client, streamers)
+        .await?
+        .into_iter()
+        .map(|(login, user)| (login, user.id))
+        .collect())
 }
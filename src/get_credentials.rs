@@ -1,21 +1,44 @@
 #[cfg(feature = "ssr")]
 use chrono::{DateTime, Utc};
-use leptos::prelude::*;
+#[cfg(feature = "ssr")]
+use reqwest::Client;
 use serde::{Deserialize, Serialize};
 #[cfg(feature = "ssr")]
-use std::sync::OnceLock;
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 #[cfg(feature = "ssr")]
 use tokio::sync::Mutex;
 
+#[cfg(feature = "ssr")]
+use crate::twitch_error::TwitchError;
+
 #[cfg(feature = "ssr")]
 struct CachedCredentials {
     credentials: CredentialsResponse,
     expiration_date: DateTime<Utc>,
 }
 
+/// App access token of the Twitch application, requested when missing or close to expire
 #[cfg(feature = "ssr")]
-static CREDENTIALS: OnceLock<Mutex<Option<CachedCredentials>>> = OnceLock::new();
+pub struct Credentials {
+    /// Base URL of the Twitch OAuth API
+    auth_url: String,
+    client_id: String,
+    client_secret: String,
+    /// File the access token is saved to, so that restarts reuse it
+    token_path: Option<PathBuf>,
+    cached: Mutex<Option<CachedCredentials>>,
+}
+
+/// Content of the token file
+#[cfg(feature = "ssr")]
+#[derive(Serialize, Deserialize)]
+struct StoredToken {
+    /// Tokens only work with the application they were issued to
+    client_id: String,
+    access_token: String,
+    expires_at: DateTime<Utc>,
+}
 
 #[cfg(feature = "ssr")]
 #[derive(Deserialize)]
@@ -34,53 +57,191 @@ struct CredentialsForm {
 }
 
 #[cfg(feature = "ssr")]
-pub async fn get_access_token() -> Result<String, ServerFnError> {
-    use chrono::Days;
-
-    let now = Utc::now();
-
-    let lock = CREDENTIALS.get_or_init(|| Mutex::new(None));
-    let mut guard = lock.lock().await;
-
-    if let Some(cached_credentials) = guard.as_ref()
-        && cached_credentials
-            .expiration_date
-            .checked_sub_days(Days::new(1))
-            .expect("No issue with UTC date")
-            > now
-    {
-        // Access token found and not expired/close to expire
-        Ok(cached_credentials.credentials.access_token.clone())
-    } else {
-        // No access token found or access token expired/close to expire
-
-        let client_id = dotenvy::var("TWITCH_CLIENT_ID").map_err(|_| ServerFnError::new("Missing TWITCH_CLIENT_ID"))?;
-        let client_secret =
-            dotenvy::var("TWITCH_CLIENT_SECRET").map_err(|_| ServerFnError::new("Missing TWITCH_CLIENT_SECRET"))?;
-
-        // Query new token
-        let credentials = reqwest::Client::new()
-            .post("https://id.twitch.tv/oauth2/token")
-            .form(&CredentialsForm {
-                client_id,
-                client_secret,
-                grant_type: "client_credentials".to_string(),
-            })
+impl Credentials {
+    /// Credentials reusing the access token saved to `token_path`, if any
+    pub fn new(auth_url: String, client_id: String, client_secret: String, token_path: Option<PathBuf>) -> Self {
+        let cached = token_path.as_deref().and_then(|path| load_token(path, &client_id));
+
+        Self {
+            auth_url,
+            client_id,
+            client_secret,
+            token_path,
+            cached: Mutex::new(cached),
+        }
+    }
+
+    pub async fn get_access_token(&self, client: &Client) -> Result<String, TwitchError> {
+        use chrono::Days;
+
+        let now = Utc::now();
+
+        let mut guard = self.cached.lock().await;
+
+        if let Some(cached_credentials) = guard.as_ref()
+            && cached_credentials
+                .expiration_date
+                .checked_sub_days(Days::new(1))
+                .expect("No issue with UTC date")
+                > now
+        {
+            // Access token found and not expired/close to expire
+            Ok(cached_credentials.credentials.access_token.clone())
+        } else {
+            // No access token found or access token expired/close to expire
+
+            // Query new token
+            let credentials = client
+                .post(format!("{}/token", self.auth_url))
+                .form(&CredentialsForm {
+                    client_id: self.client_id.clone(),
+                    client_secret: self.client_secret.clone(),
+                    grant_type: "client_credentials".to_string(),
+                })
+                .send()
+                .await?
+                .error_for_status()
+                .map_err(|e| match e.status() {
+                    // Twitch answers 400 to an unknown client ID
+                    Some(reqwest::StatusCode::BAD_REQUEST) => TwitchError::AuthRejected(e.to_string()),
+                    _ => e.into(),
+                })?
+                .json::<CredentialsResponse>()
+                .await?;
+
+            // Set credentials in cache
+            let expiration_date = Utc::now() + Duration::from_secs(credentials.expires_in);
+            let cached_credentials = CachedCredentials {
+                credentials,
+                expiration_date,
+            };
+            let access_token = cached_credentials.credentials.access_token.clone();
+            if let Some(token_path) = &self.token_path {
+                save_token(token_path, &self.client_id, &cached_credentials).await;
+            }
+
+            *guard = Some(cached_credentials);
+
+            Ok(access_token)
+        }
+    }
+
+    /// Forgets the cached access token if it is still `rejected`, so that the next call to
+    /// [`Credentials::get_access_token`] requests a new one
+    pub async fn invalidate_access_token(&self, rejected: &str) {
+        let mut guard = self.cached.lock().await;
+
+        // Another request may have replaced it already
+        if guard
+            .as_ref()
+            .is_some_and(|cached_credentials| cached_credentials.credentials.access_token == rejected)
+        {
+            *guard = None;
+        }
+    }
+
+    /// Checks the cached access token against `oauth2/validate`, forgetting it if Twitch no longer accepts it
+    pub async fn validate_access_token(&self, client: &Client) -> Result<(), TwitchError> {
+        use leptos::logging::warn;
+        use reqwest::{header::AUTHORIZATION, StatusCode};
+
+        let Some(access_token) = self
+            .cached
+            .lock()
+            .await
+            .as_ref()
+            .map(|cached_credentials| cached_credentials.credentials.access_token.clone())
+        else {
+            // Nothing to validate until a token is needed
+            return Ok(());
+        };
+
+        let response = client
+            .get(format!("{}/validate", self.auth_url))
+            .header(AUTHORIZATION, format!("OAuth {access_token}"))
             .send()
-            .await?
-            .json::<CredentialsResponse>()
             .await?;
 
-        // Set credentials in cache
-        let expiration_date = Utc::now() + Duration::from_secs(credentials.expires_in);
-        let cached_credentials = CachedCredentials {
-            credentials,
-            expiration_date,
-        };
-        let access_token = cached_credentials.credentials.access_token.clone();
+        if response.status() == StatusCode::UNAUTHORIZED {
+            warn!("Twitch no longer accepts the access token, a new one will be requested");
+            self.invalidate_access_token(&access_token).await;
+            return Ok(());
+        }
+        response.error_for_status()?;
+
+        Ok(())
+    }
+}
 
-        *guard = Some(cached_credentials);
+/// Reads the token saved by a previous run, it is validated along with the hourly checks
+#[cfg(feature = "ssr")]
+fn load_token(path: &Path, client_id: &str) -> Option<CachedCredentials> {
+    use leptos::logging::{log, warn};
+
+    let content = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return None,
+        Err(e) => {
+            warn!("Could not read the access token from {}: {e}", path.display());
+            return None;
+        }
+    };
+    let stored = match serde_json::from_str::<StoredToken>(&content) {
+        Ok(stored) => stored,
+        Err(e) => {
+            warn!("Ignoring the invalid token file {}: {e}", path.display());
+            return None;
+        }
+    };
+    if stored.client_id != client_id {
+        warn!(
+            "Ignoring the access token of another application saved to {}",
+            path.display()
+        );
+        return None;
+    }
+
+    log!("reusing the access token saved to {}", path.display());
+    Some(CachedCredentials {
+        credentials: CredentialsResponse {
+            access_token: stored.access_token,
+            expires_in: (stored.expires_at - Utc::now()).num_seconds().max(0) as u64,
+        },
+        expiration_date: stored.expires_at,
+    })
+}
+
+/// Saves the token readable by the owner only, failures are logged since the token still works in memory
+#[cfg(feature = "ssr")]
+async fn save_token(path: &Path, client_id: &str, cached_credentials: &CachedCredentials) {
+    use leptos::logging::warn;
+    use tokio::io::AsyncWriteExt;
+
+    let stored = StoredToken {
+        client_id: client_id.to_string(),
+        access_token: cached_credentials.credentials.access_token.clone(),
+        expires_at: cached_credentials.expiration_date,
+    };
+    let content = serde_json::to_vec(&stored).expect("Token serializes to JSON");
+
+    // Written next to the token file then renamed, so that another instance never reads it half written
+    let mut temp_path = path.as_os_str().to_owned();
+    temp_path.push(".tmp");
+    let result = async {
+        let mut file = tokio::fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(&temp_path)
+            .await?;
+        file.write_all(&content).await?;
+        file.sync_all().await?;
+        tokio::fs::rename(&temp_path, path).await
+    }
+    .await;
 
-        Ok(access_token)
+    if let Err(e) = result {
+        warn!("Could not save the access token to {}: {e}", path.display());
     }
 }
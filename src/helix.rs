@@ -0,0 +1,98 @@
+use futures::future::try_join_all;
+use serde::{de::DeserializeOwned, Deserialize};
+
+use crate::{twitch_client::TwitchClient, twitch_error::TwitchError};
+
+/// Maximum number of values Helix accepts for a repeated query parameter, and maximum page size
+const MAX_PER_REQUEST: usize = 100;
+
+#[derive(Debug, Deserialize)]
+struct HelixPage<T> {
+    data: Vec<T>,
+    #[serde(default)]
+    pagination: Pagination,
+}
+
+/// Response of the endpoints answering with a single object, such as `schedule`
+#[derive(Debug, Deserialize)]
+struct HelixData<T> {
+    data: T,
+}
+
+#[derive(Debug, Deserialize, Default)]
+struct Pagination {
+    cursor: Option<String>,
+}
+
+/// Queries a Helix endpoint for every value of a repeated parameter (`login`, `user_login`, ...)
+///
+/// Values are split into batches of 100 which are queried concurrently, each batch following the pagination cursor
+/// until every page has been read.
+pub async fn get_all<T: DeserializeOwned>(
+    client: &TwitchClient,
+    endpoint: &str,
+    param: &str,
+    values: &[String],
+    paginated: bool,
+) -> Result<Vec<T>, TwitchError> {
+    let batches = values
+        .chunks(MAX_PER_REQUEST)
+        .map(|batch| get_batch(client, endpoint, param, batch, paginated));
+
+    Ok(try_join_all(batches).await?.into_iter().flatten().collect())
+}
+
+async fn get_batch<T: DeserializeOwned>(
+    client: &TwitchClient,
+    endpoint: &str,
+    param: &str,
+    values: &[String],
+    paginated: bool,
+) -> Result<Vec<T>, TwitchError> {
+    let mut query = values.iter().map(|v| (param, v.as_str())).collect::<Vec<_>>();
+    let page_size = MAX_PER_REQUEST.to_string();
+    if paginated {
+        query.push(("first", page_size.as_str()));
+    }
+
+    get_pages(client, endpoint, &query, paginated).await
+}
+
+/// Queries a Helix endpoint, following the pagination cursor if `paginated`
+pub async fn get_pages<T: DeserializeOwned>(
+    client: &TwitchClient,
+    endpoint: &str,
+    query: &[(&str, &str)],
+    paginated: bool,
+) -> Result<Vec<T>, TwitchError> {
+    let url = format!("{}/{}", client.api_url(), endpoint);
+    let mut data = Vec::new();
+    let mut cursor = None::<String>;
+    loop {
+        let mut request = client.get(&url).query(query);
+        if let Some(cursor) = &cursor {
+            request = request.query(&[("after", cursor)]);
+        }
+
+        let page = client.send(request).await?.json::<HelixPage<T>>().await?;
+        data.extend(page.data);
+
+        match page.pagination.cursor {
+            Some(next) if paginated && !next.is_empty() => cursor = Some(next),
+            _ => break,
+        }
+    }
+
+    Ok(data)
+}
+
+/// Queries a Helix endpoint whose `data` is a single object rather than a list
+pub async fn get_one<T: DeserializeOwned>(
+    client: &TwitchClient,
+    endpoint: &str,
+    query: &[(&str, &str)],
+) -> Result<T, TwitchError> {
+    let request = client.get(format!("{}/{}", client.api_url(), endpoint)).query(query);
+
+    Ok(client.send(request).await?.json::<HelixData<T>>().await?.data)
+}
@@ -0,0 +1,277 @@
+use chrono::{DateTime, Utc};
+#[cfg(feature = "history")]
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "history")]
+use std::{
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+#[cfg(feature = "history")]
+use crate::fetch_streamers::{Streamer, StreamerResponse};
+
+/// Schema changes, applied in order to databases whose `user_version` is behind
+#[cfg(feature = "history")]
+const MIGRATIONS: [&str; 1] = ["
+    CREATE TABLE sessions (
+        id INTEGER PRIMARY KEY,
+        user_id TEXT NOT NULL,
+        login TEXT NOT NULL,
+        started_at TEXT NOT NULL,
+        last_seen_at TEXT NOT NULL,
+        ended_at TEXT,
+        UNIQUE (user_id, started_at)
+    );
+    CREATE TABLE session_segments (
+        session_id INTEGER NOT NULL REFERENCES sessions (id) ON DELETE CASCADE,
+        seen_at TEXT NOT NULL,
+        title TEXT NOT NULL,
+        game_name TEXT
+    );
+    CREATE INDEX session_segments_session_id ON session_segments (session_id);
+    CREATE TABLE viewer_samples (
+        session_id INTEGER NOT NULL REFERENCES sessions (id) ON DELETE CASCADE,
+        sampled_at TEXT NOT NULL,
+        viewer_count INTEGER NOT NULL
+    );
+    CREATE INDEX viewer_samples_session_id ON viewer_samples (session_id);
+"];
+
+/// Stream of a roster member, from the first poll that saw it live to the last one
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct StreamSession {
+    pub id: i64,
+    /// Twitch user ID
+    pub user_id: String,
+    /// Login at the time of the last poll
+    pub login: String,
+    /// As reported by Twitch
+    pub started_at: DateTime<Utc>,
+    pub last_seen_at: DateTime<Utc>,
+    /// Date of the last poll that saw the stream live, `None` while it is
+    pub ended_at: Option<DateTime<Utc>>,
+    /// Titles and categories of the stream, in the order they were seen
+    pub segments: Vec<SessionSegment>,
+    /// `None` when Twitch never reported a viewer count
+    pub peak_viewers: Option<u32>,
+    pub average_viewers: Option<u32>,
+}
+
+/// Title and category of a stream, until the next segment of its session
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct SessionSegment {
+    pub seen_at: DateTime<Utc>,
+    pub title: String,
+    pub game_name: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
+pub struct ViewerSample {
+    pub sampled_at: DateTime<Utc>,
+    pub viewer_count: u32,
+}
+
+/// Stream sessions of the roster members, recorded from every poll in a SQLite database
+///
+/// Clones share the connection, queries run on the blocking thread pool.
+#[cfg(feature = "history")]
+#[derive(Clone)]
+pub struct History {
+    connection: Arc<Mutex<Connection>>,
+}
+
+#[cfg(feature = "history")]
+impl History {
+    /// Opens the database at `path`, creating it if needed and migrating it to the current schema
+    pub fn open(path: &Path) -> rusqlite::Result<Self> {
+        let mut connection = Connection::open(path)?;
+        // Pages read the history while the poller writes to it
+        connection.execute_batch("PRAGMA journal_mode = WAL; PRAGMA foreign_keys = ON;")?;
+        migrate(&mut connection)?;
+
+        Ok(Self {
+            connection: Arc::new(Mutex::new(connection)),
+        })
+    }
+
+    async fn with_connection<T: Send + 'static>(
+        &self,
+        f: impl FnOnce(&mut Connection) -> rusqlite::Result<T> + Send + 'static,
+    ) -> rusqlite::Result<T> {
+        let connection = self.connection.clone();
+
+        tokio::task::spawn_blocking(move || f(&mut connection.lock().expect("History queries do not panic")))
+            .await
+            .expect("History queries do not panic")
+    }
+
+    /// Records a poll of the roster
+    ///
+    /// Live streamers extend their session, or start one when Twitch reports a new start date. Sessions of the
+    /// streamers this poll did not see live are ended as of the last poll that did.
+    pub async fn record(&self, response: &StreamerResponse) -> rusqlite::Result<()> {
+        let live = response
+            .streamers
+            .iter()
+            .filter(|s| s.is_live && s.started_at.is_some())
+            .cloned()
+            .collect::<Vec<_>>();
+        let polled_at = response.fetched_at;
+
+        self.with_connection(move |connection| {
+            let transaction = connection.transaction()?;
+            for streamer in &live {
+                record_streamer(&transaction, streamer, polled_at)?;
+            }
+            transaction.execute(
+                "UPDATE sessions SET ended_at = last_seen_at WHERE ended_at IS NULL AND last_seen_at < ?1",
+                params![polled_at],
+            )?;
+            transaction.commit()
+        })
+        .await
+    }
+
+    /// Latest sessions of a streamer, the most recent first
+    pub async fn recent_sessions(&self, user_id: &str, limit: usize) -> rusqlite::Result<Vec<StreamSession>> {
+        self.sessions(user_id, None, limit as i64).await
+    }
+
+    /// Sessions of a streamer started since `since`, the most recent first
+    pub async fn sessions_since(&self, user_id: &str, since: DateTime<Utc>) -> rusqlite::Result<Vec<StreamSession>> {
+        // SQLite reads a negative limit as no limit
+        self.sessions(user_id, Some(since), -1).await
+    }
+
+    async fn sessions(
+        &self,
+        user_id: &str,
+        since: Option<DateTime<Utc>>,
+        limit: i64,
+    ) -> rusqlite::Result<Vec<StreamSession>> {
+        let user_id = user_id.to_string();
+
+        self.with_connection(move |connection| {
+            let mut sessions = connection
+                .prepare(
+                    "SELECT s.id, s.user_id, s.login, s.started_at, s.last_seen_at, s.ended_at,
+                        MAX(v.viewer_count), CAST(ROUND(AVG(v.viewer_count)) AS INTEGER)
+                    FROM sessions s LEFT JOIN viewer_samples v ON v.session_id = s.id
+                    WHERE s.user_id = ?1 AND (?2 IS NULL OR s.started_at >= ?2)
+                    GROUP BY s.id
+                    ORDER BY s.started_at DESC
+                    LIMIT ?3",
+                )?
+                .query_map(params![user_id, since, limit], |row| {
+                    Ok(StreamSession {
+                        id: row.get(0)?,
+                        user_id: row.get(1)?,
+                        login: row.get(2)?,
+                        started_at: row.get(3)?,
+                        last_seen_at: row.get(4)?,
+                        ended_at: row.get(5)?,
+                        segments: Vec::new(),
+                        peak_viewers: row.get(6)?,
+                        average_viewers: row.get(7)?,
+                    })
+                })?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+
+            let mut segments = connection.prepare(
+                "SELECT seen_at, title, game_name FROM session_segments WHERE session_id = ?1 ORDER BY rowid",
+            )?;
+            for session in &mut sessions {
+                session.segments = segments
+                    .query_map(params![session.id], |row| {
+                        Ok(SessionSegment {
+                            seen_at: row.get(0)?,
+                            title: row.get(1)?,
+                            game_name: row.get(2)?,
+                        })
+                    })?
+                    .collect::<rusqlite::Result<_>>()?;
+            }
+
+            Ok(sessions)
+        })
+        .await
+    }
+
+    /// Viewer counts of a session, in the order they were polled
+    pub async fn viewer_samples(&self, session_id: i64) -> rusqlite::Result<Vec<ViewerSample>> {
+        self.with_connection(move |connection| {
+            connection
+                .prepare("SELECT sampled_at, viewer_count FROM viewer_samples WHERE session_id = ?1 ORDER BY rowid")?
+                .query_map(params![session_id], |row| {
+                    Ok(ViewerSample {
+                        sampled_at: row.get(0)?,
+                        viewer_count: row.get(1)?,
+                    })
+                })?
+                .collect()
+        })
+        .await
+    }
+}
+
+#[cfg(feature = "history")]
+fn migrate(connection: &mut Connection) -> rusqlite::Result<()> {
+    let version = connection.pragma_query_value(None, "user_version", |row| row.get::<_, usize>(0))?;
+    if version > MIGRATIONS.len() {
+        return Err(rusqlite::Error::SqliteFailure(
+            rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_ERROR),
+            Some(format!(
+                "schema version {version} is newer than this build, which knows up to {}",
+                MIGRATIONS.len()
+            )),
+        ));
+    }
+
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+        let transaction = connection.transaction()?;
+        transaction.execute_batch(migration)?;
+        transaction.pragma_update(None, "user_version", index + 1)?;
+        transaction.commit()?;
+    }
+
+    Ok(())
+}
+
+/// Extends the session of a live streamer, adding a segment when its title or category changed
+#[cfg(feature = "history")]
+fn record_streamer(connection: &Connection, streamer: &Streamer, polled_at: DateTime<Utc>) -> rusqlite::Result<()> {
+    // A session ended by a missed poll is resumed when Twitch still reports the same stream
+    let session_id = connection.query_row(
+        "INSERT INTO sessions (user_id, login, started_at, last_seen_at) VALUES (?1, ?2, ?3, ?4)
+        ON CONFLICT (user_id, started_at)
+        DO UPDATE SET login = excluded.login, last_seen_at = excluded.last_seen_at, ended_at = NULL
+        RETURNING id",
+        params![streamer.user_id, streamer.channel_name, streamer.started_at, polled_at],
+        |row| row.get::<_, i64>(0),
+    )?;
+
+    let title = streamer.stream_title.clone().unwrap_or_default();
+    let last_segment = connection
+        .query_row(
+            "SELECT title, game_name FROM session_segments WHERE session_id = ?1 ORDER BY rowid DESC LIMIT 1",
+            params![session_id],
+            |row| Ok((row.get::<_, String>(0)?, row.get::<_, Option<String>>(1)?)),
+        )
+        .optional()?;
+    if last_segment.as_ref() != Some(&(title.clone(), streamer.game_name.clone())) {
+        connection.execute(
+            "INSERT INTO session_segments (session_id, seen_at, title, game_name) VALUES (?1, ?2, ?3, ?4)",
+            params![session_id, polled_at, title, streamer.game_name],
+        )?;
+    }
+
+    if let Some(viewer_count) = streamer.viewer_count {
+        connection.execute(
+            "INSERT INTO viewer_samples (session_id, sampled_at, viewer_count) VALUES (?1, ?2, ?3)",
+            params![session_id, polled_at, viewer_count],
+        )?;
+    }
+
+    Ok(())
+}
@@ -1,12 +1,101 @@
+use chrono::{DateTime, Local, Utc};
+use std::time::Duration;
 use leptos::prelude::*;
 use singlestage::{Avatar, AvatarImage, Badge};
 
-use crate::fetch_streamers::{Streamer, fetch_streamers};
+use crate::fetch_streamers::{fetch_streamers, Streamer, UnavailableStreamer};
+use crate::images::{image_url, ImageSize};
+use crate::live_updates::use_live_streamers;
+use crate::reruns::{fetch_reruns, RerunKind};
+use crate::schedule::{coming_up, ScheduledStream};
+use crate::twitch_error::TwitchError;
+
+/// Scheduled streams listed under the roster
+const COMING_UP: usize = 10;
+
+/// Uptimes are shown to the minute
+const UPTIME_TICK: Duration = Duration::from_secs(60);
+
+/// Time between two dates, as "2h05" or "42 min"
+pub(crate) fn duration(from: DateTime<Utc>, to: DateTime<Utc>) -> String {
+    let minutes = (to - from).num_minutes().max(0);
+
+    if minutes < 60 {
+        format!("{minutes} min")
+    } else {
+        format!("{}h{:02}", minutes / 60, minutes % 60)
+    }
+}
+
+/// Whether the page is hydrated, views depending on the visitor's timezone waiting for it since the server does not
+/// know that timezone
+pub(crate) fn use_hydrated() -> ReadSignal<bool> {
+    let (hydrated, set_hydrated) = signal(false);
+    // Effects only run in the browser
+    Effect::new(move || set_hydrated.set(true));
+
+    hydrated
+}
+
+/// Date in the visitor's timezone, formatted as `format`
+#[component]
+pub(crate) fn LocalTime(time: DateTime<Utc>, format: &'static str) -> impl IntoView {
+    let hydrated = use_hydrated();
+
+    view! {
+        <time datetime=time.to_rfc3339()>
+            {move || hydrated.get().then(|| time.with_timezone(&Local).format(format).to_string())}
+        </time>
+    }
+}
+
+/// Time since the stream started, counting up in the visitor's browser
+#[component]
+pub(crate) fn Uptime(started_at: DateTime<Utc>) -> impl IntoView {
+    let hydrated = use_hydrated();
+    let (now, set_now) = signal(Utc::now());
+
+    // Effects only run in the browser
+    Effect::new(move || {
+        let handle = set_interval_with_handle(move || set_now.set(Utc::now()), UPTIME_TICK).ok();
+        on_cleanup(move || {
+            if let Some(handle) = handle {
+                handle.clear();
+            }
+        });
+    });
+
+    move || hydrated.get().then(|| duration(started_at, now.get()))
+}
+
+/// Banner for channels without an offline image, made of their avatar over a blurred copy of it
+#[component]
+pub(crate) fn AvatarBanner(avatar_url: String) -> impl IntoView {
+    let avatar_url = image_url(&avatar_url, ImageSize::Avatar);
+
+    view! {
+        <div class="relative flex items-center justify-center w-full h-full bg-secondary">
+            <img
+                src=avatar_url.clone()
+                alt=""
+                class="absolute inset-0 w-full h-full object-cover blur-xl opacity-40"
+            />
+            <img src=avatar_url alt="" class="relative w-20 h-20 rounded-full" />
+        </div>
+    }
+}
 
 #[component]
 fn StreamerCard(streamer: Streamer, featured: RwSignal<Option<String>>) -> impl IntoView {
     let is_live_featured = streamer.is_live;
     let channel_name_featured = streamer.channel_name.to_lowercase();
+    let preview_url = streamer
+        .thumbnail_url
+        .as_deref()
+        .map(|url| image_url(url, ImageSize::Preview))
+        .or_else(|| streamer.offline_image_url.as_deref().map(|url| image_url(url, ImageSize::Original)));
+    let next_stream = streamer.schedule.first().cloned().filter(|_| !streamer.is_live);
+    let profile_href = format!("/s/{}", streamer.channel_name);
 
     view! {
         <div
@@ -17,16 +106,21 @@ fn StreamerCard(streamer: Streamer, featured: RwSignal<Option<String>>) -> impl
                 }
             }
         >
-            <div class="relative aspect-video">
-                // Stream preview
-                <img
-                    src=format!(
-                        "https://static-cdn.jtvnw.net/previews-ttv/live_user_{}-854x480.jpg",
-                        streamer.channel_name,
-                    )
-                    alt="Stream Preview"
-                    class="rounded-lg"
-                />
+            <div class="relative aspect-video overflow-hidden rounded-lg">
+                // Stream preview, or the offline banner
+                {match preview_url {
+                    Some(preview_url) => {
+                        view! {
+                            <img
+                                src=preview_url
+                                alt="Stream Preview"
+                                class="w-full h-full object-cover"
+                            />
+                        }
+                            .into_any()
+                    }
+                    None => view! { <AvatarBanner avatar_url=streamer.avatar_url.clone() /> }.into_any(),
+                }}
                 // Stream status
                 {if streamer.is_live {
                     view! {
@@ -47,6 +141,19 @@ fn StreamerCard(streamer: Streamer, featured: RwSignal<Option<String>>) -> impl
                         </Badge>
                     }
                 }}
+                // Uptime
+                {streamer
+                    .started_at
+                    .map(|started_at| {
+                        view! {
+                            <Badge
+                                class="absolute bottom-2 left-2 bg-secondary/80"
+                                variant="secondary"
+                            >
+                                <Uptime started_at />
+                            </Badge>
+                        }
+                    })}
                 // Viewer Count
                 {streamer
                     .viewer_count
@@ -67,7 +174,7 @@ fn StreamerCard(streamer: Streamer, featured: RwSignal<Option<String>>) -> impl
             <div class="flex flex-row items-center mx-2 my-3">
                 <Avatar class="mr-2 w-9 h-9">
                     <AvatarImage
-                        src=streamer.avatar_url
+                        src=image_url(&streamer.avatar_url, ImageSize::SmallAvatar)
                         alt=streamer.channel_name.as_ref()
                         class="rounded-full w-full h-full object-cover"
                     />
@@ -77,26 +184,236 @@ fn StreamerCard(streamer: Streamer, featured: RwSignal<Option<String>>) -> impl
                     <p class="text-md font-semibold line-clamp-1">
                         {streamer.stream_title}
                     </p>
-                    // Streamer display name
-                    <p class="text-sm text-muted-foreground">
+                    // Streamer display name, linking to their page
+                    <a href=profile_href class="text-sm text-muted-foreground hover:underline">
                         {streamer.display_name}
-                    </p>
+                    </a>
+                    // Category
+                    {streamer
+                        .game_name
+                        .map(|game_name| {
+                            view! {
+                                <p class="text-sm text-muted-foreground line-clamp-1">
+                                    {game_name}
+                                </p>
+                            }
+                        })}
+                    // Next scheduled stream, while offline
+                    {next_stream
+                        .map(|next_stream| {
+                            view! {
+                                <p class="text-sm text-muted-foreground line-clamp-1">
+                                    "Next stream: "
+                                    <LocalTime time=next_stream.start_time format="%a %H:%M" />
+                                    {(!next_stream.title.is_empty())
+                                        .then(|| format!(" — {}", next_stream.title))}
+                                </p>
+                            }
+                        })}
                 </div>
             </div>
+            // Language and tags
+            <div class="flex flex-wrap gap-1 mx-2 mb-3">
+                {streamer
+                    .language
+                    .map(|language| {
+                        view! {
+                            <Badge variant="outline">{language.to_uppercase()}</Badge>
+                        }
+                    })}
+                {streamer
+                    .is_mature
+                    .then(|| {
+                        view! {
+                            <Badge variant="outline">"18+"</Badge>
+                        }
+                    })}
+                {streamer
+                    .tags
+                    .into_iter()
+                    .map(|tag| {
+                        view! {
+                            <Badge variant="secondary">{tag}</Badge>
+                        }
+                    })
+                    .collect_view()}
+            </div>
+        </div>
+    }
+}
+
+#[component]
+fn UnavailableCard(streamer: UnavailableStreamer) -> impl IntoView {
+    view! {
+        <div class="w-72 rounded-lg opacity-50 grayscale" title=streamer.login>
+            <div class="flex items-center justify-center aspect-video rounded-lg bg-secondary">
+                <Badge class="bg-secondary/80" variant="secondary">
+                    "UNAVAILABLE"
+                </Badge>
+            </div>
+            <div class="flex flex-col mx-2 my-3">
+                <p class="text-md font-semibold line-clamp-1">
+                    {streamer.reason.user_message()}
+                </p>
+                <p class="text-sm text-muted-foreground">
+                    {streamer.display_name}
+                </p>
+            </div>
+        </div>
+    }
+}
+
+#[component]
+fn ScheduledRow(streamer: Streamer, stream: ScheduledStream) -> impl IntoView {
+    view! {
+        <div class="flex flex-row justify-between gap-4 py-3 border-b border-border">
+            <div class="flex flex-col">
+                <p class="text-md font-semibold line-clamp-1">{stream.title}</p>
+                <a
+                    href=format!("/s/{}", streamer.channel_name)
+                    class="text-sm text-muted-foreground hover:underline"
+                >
+                    {streamer.display_name}
+                </a>
+            </div>
+            <div class="flex flex-col items-end shrink-0 text-sm text-muted-foreground">
+                <p>
+                    <LocalTime time=stream.start_time format="%a %d/%m %H:%M" />
+                </p>
+                <p>{stream.category}</p>
+            </div>
+        </div>
+    }
+}
+
+#[component]
+pub(crate) fn ErrorState(error: TwitchError, on_retry: impl Fn() + 'static) -> impl IntoView {
+    view! {
+        <div class="flex flex-col items-center justify-center gap-4 w-full h-full">
+            <p class="text-xl font-semibold">{error.user_message()}</p>
+            <button
+                class="px-4 py-2 rounded-md bg-secondary text-secondary-foreground hover:bg-secondary/80"
+                on:click=move |_| on_retry()
+            >
+                "Réessayer"
+            </button>
         </div>
     }
 }
 
+/// Past broadcasts and clips of the roster, played one after the other while nobody is live
+#[component]
+fn RerunPlayer(base_addr: String) -> impl IntoView {
+    let reruns = Resource::new(|| (), |_| fetch_reruns());
+    let position = RwSignal::new(0usize);
+    let current = move || {
+        reruns
+            .get()
+            .and_then(Result::ok)
+            .filter(|reruns| !reruns.is_empty())
+            .map(|reruns| reruns[position.get() % reruns.len()].clone())
+    };
+
+    // Moves on to the next rerun once this one played, the embeds not telling when they end
+    Effect::new(move || {
+        let Some(rerun) = current() else {
+            return;
+        };
+        let handle = set_timeout_with_handle(move || position.update(|p| *p += 1), rerun.play_time()).ok();
+        on_cleanup(move || {
+            if let Some(handle) = handle {
+                handle.clear();
+            }
+        });
+    });
+
+    view! {
+        <Suspense fallback=move || {
+            view! { <p>"Loading..."</p> }
+        }>
+            {move || match current() {
+                Some(rerun) => {
+                    view! {
+                        <div class="relative w-full h-full">
+                            <iframe
+                                src=rerun.embed_url(&base_addr)
+                                class="w-full h-full"
+                                allowfullscreen="true"
+                            ></iframe>
+                            <div class="absolute top-2 left-2 flex flex-row items-center gap-2">
+                                <Badge class="bg-secondary/80" variant="secondary">
+                                    "RERUN"
+                                </Badge>
+                                {(rerun.kind == RerunKind::Clip)
+                                    .then(|| {
+                                        view! {
+                                            <Badge class="bg-secondary/80" variant="secondary">
+                                                "CLIP"
+                                            </Badge>
+                                        }
+                                    })}
+                                <a
+                                    href=format!("/s/{}", rerun.channel_name)
+                                    class="px-2 rounded-md bg-secondary/80 text-sm line-clamp-1 hover:underline"
+                                >
+                                    {format!("{} — {}", rerun.display_name, rerun.title)}
+                                </a>
+                            </div>
+                            <button
+                                class="absolute top-2 right-2 px-2 rounded-md bg-secondary/80 text-sm hover:bg-secondary"
+                                on:click=move |_| position.update(|p| *p += 1)
+                            >
+                                "Suivant"
+                            </button>
+                        </div>
+                    }
+                        .into_any()
+                }
+                None => {
+                    view! {
+                        <div class="flex items-center justify-center w-full h-full">
+                            <p class="text-xl font-semibold">"Frérot y'a personne qui stream"</p>
+                        </div>
+                    }
+                        .into_any()
+                }
+            }}
+        </Suspense>
+    }
+}
+
 #[component]
 pub fn HomePage() -> impl IntoView {
     let streamer_response = Resource::new(|| (), |_| fetch_streamers());
+    let live_response = use_live_streamers();
+    // Pushed snapshots take over the initially loaded data
+    let current_response = move || live_response.get().map(Ok).or_else(|| streamer_response.get());
+
     let featured = RwSignal::new(None);
+    // Reruns are only fetched when there is no live stream to feature
+    let nobody_live = Memo::new(move |_| {
+        current_response()
+            .and_then(Result::ok)
+            .is_some_and(|response| response.streamers.iter().all(|s| !s.is_live))
+    });
     Effect::new(move || {
-        if let Some(Ok(streamer_response)) = streamer_response.get()
-            && let Some(first_streamer) = streamer_response.streamers.first()
-            && first_streamer.is_live
-        {
-            featured.set(Some(first_streamer.channel_name.to_lowercase()))
+        if let Some(Ok(streamer_response)) = current_response() {
+            // Keep the featured stream while it is live, otherwise feature the most watched one
+            let featured_is_live = featured.get_untracked().is_some_and(|channel_name: String| {
+                streamer_response
+                    .streamers
+                    .iter()
+                    .any(|s| s.is_live && s.channel_name.to_lowercase() == channel_name)
+            });
+            if !featured_is_live {
+                featured.set(
+                    streamer_response
+                        .streamers
+                        .first()
+                        .filter(|s| s.is_live)
+                        .map(|s| s.channel_name.to_lowercase()),
+                );
+            }
         }
     });
 
@@ -108,17 +425,19 @@ pub fn HomePage() -> impl IntoView {
                     view! { <p>"Loading..."</p> }
                 }>
                     {move || {
+                        // Only the initial load is read here, so that pushed snapshots don't reload the player
                         let data = streamer_response.get();
                         match data {
                             Some(Ok(response)) => {
-                                match featured.get() {
+                                let base_addr = response.base_addr;
+                                (move || match featured.get() {
                                     Some(channel_name) => {
 
                                         view! {
                                             <iframe
                                                 src=format!(
                                                     "https://player.twitch.tv/?channel={channel_name}&parent={}",
-                                                    response.base_addr,
+                                                    base_addr,
                                                 )
                                                 class="w-full h-full"
                                                 // height="425"
@@ -130,27 +449,21 @@ pub fn HomePage() -> impl IntoView {
                                         }
                                             .into_any()
                                     }
-                                    None => {
-                                        view! {
-                                            <div class="flex items-center justify-center w-full h-full">
-                                                <p class="text-xl font-semibold">
-                                                    "Frérot y'a personne qui stream"
-                                                </p>
-                                            </div>
-                                        }
-                                            .into_any()
+                                    None if nobody_live.get() => {
+                                        view! { <RerunPlayer base_addr=base_addr.clone() /> }.into_any()
                                     }
-                                }
+                                    // A live stream is about to be featured
+                                    None => ().into_any(),
+                                })
+                                    .into_any()
                             }
-                            _ => {
-
+                            Some(Err(error)) => {
                                 view! {
-                                    <div class="flex items-center justify-center w-full h-full">
-                                        <p class="text-xl font-semibold">"CKC"</p>
-                                    </div>
+                                    <ErrorState error on_retry=move || streamer_response.refetch() />
                                 }
                                     .into_any()
                             }
+                            None => ().into_any(),
                         }
                     }}
                 </Suspense>
@@ -162,12 +475,27 @@ pub fn HomePage() -> impl IntoView {
                     view! { <p>"Loading streamers..."</p> }
                 }>
                     {move || {
-                        streamer_response
-                            .get()
+                        current_response()
                             .map(|result| {
                                 result
                                     .map(|streamer_response| {
+                                        // Only shown when Twitch is down, the data being usually a minute old at most
+                                        let data_as_of = streamer_response
+                                            .stale
+                                            .then_some(streamer_response.fetched_at);
                                         view! {
+                                            {data_as_of
+                                                .map(|time| {
+                                                    view! {
+                                                        <p
+                                                            class="text-sm text-muted-foreground"
+                                                            title="Twitch ne répond pas, les données seront mises à jour dès son retour"
+                                                        >
+                                                            "Data as of "
+                                                            <LocalTime time format="%H:%M" />
+                                                        </p>
+                                                    }
+                                                })}
                                             <div class="grid grid-cols-4 gap-x-4 gap-y-8 w-full my-4">
                                                 {streamer_response
                                                     .streamers
@@ -178,6 +506,18 @@ pub fn HomePage() -> impl IntoView {
                                                         }
                                                     } )
                                                     .collect_view()}
+                                                // Roster members Twitch could not resolve, last
+                                                {streamer_response
+                                                    .show_unavailable
+                                                    .then(|| {
+                                                        streamer_response
+                                                            .unavailable
+                                                            .into_iter()
+                                                            .map(|streamer| {
+                                                                view! { <UnavailableCard streamer /> }
+                                                            })
+                                                            .collect_view()
+                                                    })}
                                             </div>
                                         }
                                     })
@@ -186,6 +526,44 @@ pub fn HomePage() -> impl IntoView {
                     }}
                 </Suspense>
             </div>
+            // Scheduled streams of the whole roster
+            <div class="my-12">
+                <div class="flex flex-row items-baseline justify-between">
+                    <h2 class="text-xl font-bold">"Coming up"</h2>
+                    <a href="/schedule" class="text-sm hover:underline">
+                        "Full week"
+                    </a>
+                </div>
+                <Suspense fallback=move || {
+                    view! { <p>"Loading schedules..."</p> }
+                }>
+                    {move || {
+                        current_response()
+                            .and_then(Result::ok)
+                            .map(|streamer_response| {
+                                let streams = coming_up(&streamer_response.streamers)
+                                    .into_iter()
+                                    .take(COMING_UP)
+                                    .map(|(streamer, stream)| (streamer.clone(), stream.clone()))
+                                    .collect::<Vec<_>>();
+                                if streams.is_empty() {
+                                    view! {
+                                        <p class="my-4 text-muted-foreground">
+                                            "Rien de prévu cette semaine"
+                                        </p>
+                                    }
+                                        .into_any()
+                                } else {
+                                    streams
+                                        .into_iter()
+                                        .map(|(streamer, stream)| view! { <ScheduledRow streamer stream /> })
+                                        .collect_view()
+                                        .into_any()
+                                }
+                            })
+                    }}
+                </Suspense>
+            </div>
         </div>
     }
 }
@@ -0,0 +1,276 @@
+#[cfg(feature = "ssr")]
+use axum::{
+    extract::{Path, State},
+    http::{
+        header::{CACHE_CONTROL, CONTENT_TYPE, ETAG, IF_NONE_MATCH},
+        HeaderMap, StatusCode,
+    },
+    response::{IntoResponse, Response},
+};
+#[cfg(feature = "ssr")]
+use leptos::logging::warn;
+#[cfg(feature = "ssr")]
+use sha2::{Digest, Sha256};
+#[cfg(feature = "ssr")]
+use std::time::{Duration, SystemTime};
+
+#[cfg(feature = "ssr")]
+use crate::{config::Config, state::AppState, twitch_client::TwitchClient};
+
+/// Twitch images served from the server's cache, so that visitors never reach the Twitch CDN
+const IMAGES_PATH: &str = "/images";
+
+/// Route of the image proxy, as `/images/70x70/jtv_user_pictures/...-profile_image-300x300.png`
+pub const IMAGE_ROUTE: &str = "/images/{size}/{*path}";
+
+/// Host of the Twitch images, the only one the proxy fetches from
+pub(crate) const TWITCH_CDN: &str = "https://static-cdn.jtvnw.net";
+
+/// Directories of the CDN holding the images the pages show: avatars and offline banners, then stream previews
+const PROXIED_DIRS: [&str; 2] = ["jtv_user_pictures/", "previews-ttv/"];
+
+/// Lifetime of the stream previews, which Twitch renews every 5 minutes
+#[cfg(feature = "ssr")]
+const PREVIEW_MAX_AGE: Duration = Duration::from_secs(300);
+
+/// Lifetime of the other images, Twitch gives them a new URL when they change
+#[cfg(feature = "ssr")]
+const MAX_AGE: Duration = Duration::from_secs(86400);
+
+/// Cached images not requested for this long are removed
+#[cfg(feature = "ssr")]
+const PRUNE_AFTER: Duration = Duration::from_secs(7 * 86400);
+
+/// Size variants of the proxied images
+///
+/// Twitch renders its images in the size found at the end of their file name, as `-300x300.png`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageSize {
+    /// Avatar next to the stream title
+    SmallAvatar,
+    /// Avatar of the banner of channels without an offline image
+    Avatar,
+    /// Stream preview
+    Preview,
+    /// As uploaded, for the offline banners which Twitch only serves in 1920x1080
+    Original,
+}
+
+impl ImageSize {
+    #[cfg(feature = "ssr")]
+    const ALL: [Self; 4] = [Self::SmallAvatar, Self::Avatar, Self::Preview, Self::Original];
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::SmallAvatar => "70x70",
+            Self::Avatar => "150x150",
+            Self::Preview => "854x480",
+            Self::Original => "original",
+        }
+    }
+
+    #[cfg(feature = "ssr")]
+    fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|size| size.name() == name)
+    }
+
+    /// Path of the image in this size, `None` when its file name carries no size
+    #[cfg(feature = "ssr")]
+    fn apply(self, path: &str) -> Option<String> {
+        if self == Self::Original {
+            return Some(path.to_string());
+        }
+
+        let (stem, extension) = path.rsplit_once('.')?;
+        let (name, size) = stem.rsplit_once('-')?;
+        let (width, height) = size.split_once('x')?;
+        if width.parse::<u32>().is_err() || height.parse::<u32>().is_err() {
+            return None;
+        }
+
+        Some(format!("{name}-{}.{extension}", self.name()))
+    }
+}
+
+/// URL of a Twitch image through the proxy, images from anywhere else are left as is
+pub fn image_url(twitch_url: &str, size: ImageSize) -> String {
+    match twitch_url
+        .strip_prefix(TWITCH_CDN)
+        .and_then(|path| path.strip_prefix('/'))
+    {
+        Some(path) if is_proxied(path) => format!("{IMAGES_PATH}/{}/{path}", size.name()),
+        _ => twitch_url.to_string(),
+    }
+}
+
+/// Whether the proxy serves this path of the CDN, so that it cannot be used to fetch and cache anything else
+fn is_proxied(path: &str) -> bool {
+    PROXIED_DIRS.iter().any(|dir| path.starts_with(dir))
+        && path
+            .split('/')
+            .all(|segment| !segment.is_empty() && segment != "." && segment != "..")
+}
+
+/// Serves a Twitch image from the disk cache, fetching it when missing or outdated
+///
+/// Outdated images are still served when the CDN fails, browsers revalidate them with the `ETag`.
+#[cfg(feature = "ssr")]
+pub async fn image(
+    State(app): State<AppState>,
+    Path((size, path)): Path<(String, String)>,
+    headers: HeaderMap,
+) -> Response {
+    if !is_proxied(&path) {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    let Some(content_type) = content_type(&path) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let Some(path) = ImageSize::from_name(&size).and_then(|size| size.apply(&path)) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let max_age = if path.starts_with("previews-ttv/") {
+        PREVIEW_MAX_AGE
+    } else {
+        MAX_AGE
+    };
+
+    let cache_path = app.config.image_cache_dir.join(hex::encode(Sha256::digest(&path)));
+    let cached = read_cached(&cache_path).await;
+    let body = match cached {
+        Some((body, age)) if age < max_age => body,
+        cached => match fetch(&app.twitch, &app.config.twitch_cdn_url, &path).await {
+            Ok(body) => {
+                store(&cache_path, &body).await;
+                body
+            }
+            Err(e) => match cached {
+                Some((body, _)) => {
+                    warn!("Could not refresh the Twitch image {path}, serving the cached one: {e}");
+                    body
+                }
+                None if e.status() == Some(reqwest::StatusCode::NOT_FOUND) => {
+                    return StatusCode::NOT_FOUND.into_response();
+                }
+                None => {
+                    warn!("Could not fetch the Twitch image {path}: {e}");
+                    return StatusCode::BAD_GATEWAY.into_response();
+                }
+            },
+        },
+    };
+
+    let etag = format!("\"{}\"", hex::encode(&Sha256::digest(&body)[..16]));
+    let cache_control = format!("public, max-age={}", max_age.as_secs());
+    let not_modified = headers
+        .get(IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| {
+            value
+                .split(',')
+                .map(|tag| tag.trim().trim_start_matches("W/"))
+                .any(|tag| tag == etag || tag == "*")
+        });
+
+    if not_modified {
+        (StatusCode::NOT_MODIFIED, [(CACHE_CONTROL, cache_control), (ETAG, etag)]).into_response()
+    } else {
+        (
+            [
+                (CONTENT_TYPE, content_type.to_string()),
+                (CACHE_CONTROL, cache_control),
+                (ETAG, etag),
+            ],
+            body,
+        )
+            .into_response()
+    }
+}
+
+/// Type of the images Twitch serves, other files are not proxied
+#[cfg(feature = "ssr")]
+fn content_type(path: &str) -> Option<&'static str> {
+    match path.rsplit_once('.')?.1 {
+        "png" => Some("image/png"),
+        "jpg" | "jpeg" => Some("image/jpeg"),
+        "webp" => Some("image/webp"),
+        "gif" => Some("image/gif"),
+        _ => None,
+    }
+}
+
+#[cfg(feature = "ssr")]
+async fn fetch(client: &TwitchClient, cdn_url: &str, path: &str) -> Result<Vec<u8>, reqwest::Error> {
+    let response = client
+        .get(format!("{cdn_url}/{path}"))
+        .send()
+        .await?
+        .error_for_status()?;
+
+    Ok(response.bytes().await?.to_vec())
+}
+
+/// Content and age of a cached image
+#[cfg(feature = "ssr")]
+async fn read_cached(path: &std::path::Path) -> Option<(Vec<u8>, Duration)> {
+    let modified = tokio::fs::metadata(path).await.ok()?.modified().ok()?;
+    let body = tokio::fs::read(path).await.ok()?;
+
+    Some((body, SystemTime::now().duration_since(modified).unwrap_or_default()))
+}
+
+/// Saves an image to the cache, failures are logged since the image is served anyway
+#[cfg(feature = "ssr")]
+async fn store(path: &std::path::Path, body: &[u8]) {
+    // Written next to the image then renamed, so that concurrent requests never read it half written
+    let mut temp_path = path.as_os_str().to_owned();
+    temp_path.push(format!(".{}.tmp", rand::random::<u32>()));
+    let result = async {
+        if let Some(dir) = path.parent() {
+            tokio::fs::create_dir_all(dir).await?;
+        }
+        tokio::fs::write(&temp_path, body).await?;
+        tokio::fs::rename(&temp_path, path).await
+    }
+    .await;
+
+    if let Err(e) = result {
+        warn!("Could not cache the Twitch image to {}: {e}", path.display());
+    }
+}
+
+/// Removes the cached images nobody requested for a week, such as the avatars streamers replaced
+#[cfg(feature = "ssr")]
+pub fn spawn_pruner(config: &Config) {
+    let dir = config.image_cache_dir.clone();
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(86400));
+
+        loop {
+            interval.tick().await;
+            if let Err(e) = prune(&dir).await {
+                warn!("Could not prune the image cache {}: {e}", dir.display());
+            }
+        }
+    });
+}
+
+#[cfg(feature = "ssr")]
+async fn prune(dir: &std::path::Path) -> std::io::Result<()> {
+    let mut entries = match tokio::fs::read_dir(dir).await {
+        Ok(entries) => entries,
+        // Nothing cached yet
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    };
+
+    while let Some(entry) = entries.next_entry().await? {
+        let modified = entry.metadata().await?.modified()?;
+        if SystemTime::now().duration_since(modified).unwrap_or_default() > PRUNE_AFTER {
+            tokio::fs::remove_file(entry.path()).await?;
+        }
+    }
+
+    Ok(())
+}
@@ -1,7 +1,41 @@
+#[cfg(feature = "ssr")]
+pub mod admin;
 pub mod app;
+pub mod calendar;
+#[cfg(feature = "ssr")]
+pub mod config;
+#[cfg(feature = "ssr")]
+pub mod eventsub;
+pub mod fetch_streamer_details;
 pub mod fetch_streamers;
 pub mod get_credentials;
+#[cfg(feature = "ssr")]
+pub mod helix;
+pub mod history;
 pub mod home_page;
+pub mod images;
+pub mod live_updates;
+#[cfg(feature = "ssr")]
+mod login_cache;
+#[cfg(feature = "mock-twitch")]
+pub mod mock_twitch;
+#[cfg(feature = "ssr")]
+pub mod poller;
+#[cfg(feature = "ssr")]
+pub mod rate_limit;
+pub mod reruns;
+#[cfg(feature = "ssr")]
+pub mod roster;
+pub mod schedule;
+pub mod schedule_page;
+#[cfg(feature = "ssr")]
+pub mod state;
+#[cfg(feature = "ssr")]
+pub mod status;
+pub mod streamer_page;
+#[cfg(feature = "ssr")]
+pub mod twitch_client;
+pub mod twitch_error;
 
 #[cfg(feature = "hydrate")]
 #[wasm_bindgen::prelude::wasm_bindgen]
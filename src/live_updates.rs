@@ -0,0 +1,68 @@
+#[cfg(feature = "ssr")]
+use axum::response::sse::{Event, KeepAlive, Sse};
+#[cfg(feature = "ssr")]
+use futures::{stream, Stream, StreamExt};
+use leptos::prelude::*;
+
+use crate::fetch_streamers::StreamerResponse;
+#[cfg(feature = "ssr")]
+use crate::poller;
+
+/// Server-Sent Events stream of roster snapshots
+pub const STREAMER_EVENTS_PATH: &str = "/api/streamers/events";
+
+/// Name of the events carrying a JSON `StreamerResponse`
+const SNAPSHOT_EVENT: &str = "streamers";
+
+/// Pushes a snapshot every time the poller sees the roster change
+#[cfg(feature = "ssr")]
+pub async fn streamer_events() -> Sse<impl Stream<Item = Result<Event, axum::Error>>> {
+    let mut receiver = poller::subscribe();
+    // Send the current snapshot right away, it may have changed since the page was rendered
+    receiver.mark_changed();
+
+    let snapshots = stream::unfold(receiver, |mut receiver| async move {
+        receiver.changed().await.ok()?;
+        let snapshot = receiver.borrow_and_update().clone();
+
+        Some((snapshot, receiver))
+    });
+    let events = snapshots.filter_map(|snapshot| async move {
+        snapshot.map(|snapshot| Event::default().event(SNAPSHOT_EVENT).json_data(snapshot))
+    });
+
+    Sse::new(events).keep_alive(KeepAlive::default())
+}
+
+/// Roster snapshots pushed by the server, `None` until the first one is received
+pub fn use_live_streamers() -> ReadSignal<Option<StreamerResponse>> {
+    #[cfg_attr(not(feature = "hydrate"), allow(unused_variables))]
+    let (live_response, set_live_response) = signal(None);
+
+    #[cfg(feature = "hydrate")]
+    Effect::new(move || {
+        use send_wrapper::SendWrapper;
+        use wasm_bindgen::{closure::Closure, JsCast};
+        use web_sys::{EventSource, MessageEvent};
+
+        let Ok(source) = EventSource::new(STREAMER_EVENTS_PATH) else {
+            return;
+        };
+
+        let on_snapshot = Closure::<dyn Fn(MessageEvent)>::new(move |event: MessageEvent| {
+            if let Some(data) = event.data().as_string()
+                && let Ok(response) = serde_json::from_str::<StreamerResponse>(&data)
+            {
+                set_live_response.set(Some(response));
+            }
+        });
+        let _ = source.add_event_listener_with_callback(SNAPSHOT_EVENT, on_snapshot.as_ref().unchecked_ref());
+        // The browser reconnects on its own, so the listener lives as long as the page
+        on_snapshot.forget();
+
+        let source = SendWrapper::new(source);
+        on_cleanup(move || source.close());
+    });
+
+    live_response
+}
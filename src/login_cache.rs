@@ -0,0 +1,48 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+/// Cache of Twitch data keyed by lowercase login or user ID, each entry expiring on its own
+///
+/// Absent values are cached as well, so that offline or unknown channels are not queried on every request.
+pub struct LoginCache<V> {
+    ttl: Duration,
+    entries: HashMap<String, (Instant, Option<V>)>,
+}
+
+impl<V: Clone> LoginCache<V> {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: HashMap::new(),
+        }
+    }
+
+    /// Returns the fresh cached values for `logins`, and the logins that need to be fetched
+    pub fn lookup<'a>(&self, logins: impl IntoIterator<Item = &'a str>) -> (HashMap<String, V>, Vec<String>) {
+        let mut found = HashMap::new();
+        let mut missing = Vec::new();
+
+        for login in logins {
+            match self.entries.get(login) {
+                Some((inserted_at, value)) if inserted_at.elapsed() < self.ttl => {
+                    if let Some(value) = value {
+                        found.insert(login.to_string(), value.clone());
+                    }
+                }
+                _ => missing.push(login.to_string()),
+            }
+        }
+
+        (found, missing)
+    }
+
+    pub fn insert(&mut self, login: String, value: Option<V>) {
+        self.entries.insert(login, (Instant::now(), value));
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
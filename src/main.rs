@@ -1,12 +1,44 @@
 #[cfg(feature = "ssr")]
 #[tokio::main]
 async fn main() {
-    use axum::Router;
+    use axum::{
+        middleware,
+        routing::{get, post},
+        Router,
+    };
     use leptos::logging::log;
     use leptos::prelude::*;
     use leptos_axum::{generate_route_list, LeptosRoutes};
     use tower_http::set_header::SetResponseHeaderLayer;
-    use webtv::app::*;
+    use webtv::{
+        admin::require_admin,
+        app::*,
+        calendar::{calendar, CALENDAR_PATH},
+        config::Config,
+        eventsub::{self, eventsub_webhook, EVENTSUB_PATH},
+        images::{self, image, IMAGE_ROUTE},
+        live_updates::{streamer_events, STREAMER_EVENTS_PATH},
+        poller,
+        rate_limit::{rate_limit_status, RATE_LIMIT_PATH},
+        roster,
+        state::AppState,
+        status::{status, STATUS_PATH},
+    };
+
+    // Fail fast on invalid settings rather than on the first page view
+    let config = Config::load().unwrap_or_else(|e| panic!("Invalid configuration: {e}"));
+    let state = AppState::new(config).unwrap_or_else(|e| panic!("Could not start the app services: {e}"));
+    let roster = roster::init(&state.config.roster_path).unwrap_or_else(|e| panic!("Invalid roster: {e}"));
+    log!("loaded {} streamers from roster", roster.streamers.len());
+    roster::spawn_watcher(state.clone());
+    poller::spawn(state.clone());
+    state.twitch.spawn_validator();
+    images::spawn_pruner(&state.config);
+    if state.config.eventsub.is_some() {
+        eventsub::spawn_sync(state.clone());
+    } else {
+        log!("EventSub disabled, live status changes are only seen when polling");
+    }
 
     let conf = get_configuration(None).unwrap();
     let addr = conf.leptos_options.site_addr;
@@ -26,11 +58,12 @@ async fn main() {
 
     let csp_value = [
         "default-src 'self'",
-        "frame-src https://player.twitch.tv https://www.twitch.tv https://twitch.tv https://embed.twitch.tv",
+        "frame-src https://player.twitch.tv https://www.twitch.tv https://twitch.tv https://embed.twitch.tv https://clips.twitch.tv",
         // unsafe-inline and unsafe-eval required by Twitch embed
         "script-src 'self' 'unsafe-inline' 'unsafe-eval' https://embed.twitch.tv https://player.twitch.tv https://static.twitchcdn.net",
         "style-src 'self' 'unsafe-inline'",
-        "img-src 'self' https://*.twitch.tv https://static-cdn.jtvnw.net data: blob:",
+        // Twitch images go through the image proxy
+        "img-src 'self' data: blob:",
         connect_src,
         "media-src 'self' https://*.twitch.tv https://*.ttvnw.net blob:",
         "worker-src 'self' blob:",
@@ -38,7 +71,17 @@ async fn main() {
     .join("; ");
 
     let app = Router::new()
-        .leptos_routes(&leptos_options, routes, {
+        .route(STREAMER_EVENTS_PATH, get(streamer_events))
+        .route(EVENTSUB_PATH, post(eventsub_webhook).with_state(state.clone()))
+        .merge(
+            Router::new()
+                .route(RATE_LIMIT_PATH, get(rate_limit_status))
+                .route(STATUS_PATH, get(status))
+                .layer(middleware::from_fn_with_state(state.clone(), require_admin)),
+        )
+        .route(IMAGE_ROUTE, get(image).with_state(state.clone()))
+        .route(CALENDAR_PATH, get(calendar))
+        .leptos_routes_with_context(&leptos_options, routes, move || provide_context(state.clone()), {
             let leptos_options = leptos_options.clone();
             move || shell(leptos_options.clone())
         })
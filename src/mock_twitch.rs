@@ -0,0 +1,795 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::{HeaderMap, HeaderValue, StatusCode},
+    middleware,
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use chrono::{DateTime, Utc};
+use serde_json::{json, Value};
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+pub const MOCK_CLIENT_ID: &str = "mock-client-id";
+pub const MOCK_CLIENT_SECRET: &str = "mock-client-secret";
+/// Prefix of the access tokens issued by the mock, followed by a sequence number
+pub const MOCK_ACCESS_TOKEN: &str = "mock-access-token";
+
+/// Points in the Helix rate limit bucket
+const RATE_LIMIT: u32 = 800;
+
+/// Shared by every mock, so that a token cached by the app stays valid when a test starts a new mock
+static TOKENS_ISSUED: AtomicUsize = AtomicUsize::new(0);
+
+/// Local stand-in for the Twitch API, serving `oauth2/token`, `oauth2/validate`, `helix/users`, `helix/streams`,
+/// `helix/schedule`, `helix/videos`, `helix/clips`, `helix/eventsub/subscriptions` and the images of the CDN
+///
+/// Users, streams and failures are scripted by tests, and every request is counted per endpoint.
+#[derive(Clone)]
+pub struct MockTwitch {
+    url: String,
+    state: Arc<Mutex<MockState>>,
+}
+
+#[derive(Default)]
+struct MockState {
+    users: Vec<Value>,
+    streams: Vec<Value>,
+    /// Schedules keyed by user ID, users missing here never set one
+    schedules: HashMap<String, MockSchedule>,
+    videos: Vec<Value>,
+    clips: Vec<Value>,
+    subscriptions: Vec<Value>,
+    created_subscriptions: usize,
+    /// Tokens issued before this one are revoked
+    first_valid_token: usize,
+    /// Remaining points and refill date of the rate limit bucket, full when `None`
+    bucket: Option<(u32, DateTime<Utc>)>,
+    page_size: Option<usize>,
+    failures: HashMap<String, VecDeque<StatusCode>>,
+    requests: HashMap<String, usize>,
+    /// Paths of the images requested from the CDN
+    images: Vec<String>,
+}
+
+#[derive(Default)]
+struct MockSchedule {
+    segments: Vec<Value>,
+    vacation: Option<Value>,
+}
+
+type SharedState = Arc<Mutex<MockState>>;
+
+impl MockTwitch {
+    /// Starts the server on a random local port
+    pub async fn start() -> Self {
+        let state = SharedState::default();
+
+        let helix = Router::new()
+            .route("/helix/users", get(users))
+            .route("/helix/streams", get(streams))
+            .route("/helix/schedule", get(schedule))
+            .route("/helix/videos", get(videos))
+            .route("/helix/clips", get(clips))
+            .route(
+                "/helix/eventsub/subscriptions",
+                get(subscriptions).post(create_subscription).delete(delete_subscription),
+            )
+            .layer(middleware::map_response_with_state(state.clone(), rate_limit_headers));
+        let app = Router::new()
+            .route("/oauth2/token", post(token))
+            .route("/oauth2/validate", get(validate))
+            .route("/cdn/{*path}", get(image))
+            .merge(helix)
+            .with_state(state.clone());
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("Mock Twitch server can bind");
+        let url = format!("http://{}", listener.local_addr().expect("Listener has an address"));
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        Self { url, state }
+    }
+
+    /// Value for `TWITCH_API_URL`
+    pub fn api_url(&self) -> String {
+        format!("{}/helix", self.url)
+    }
+
+    /// Value for `TWITCH_AUTH_URL`
+    pub fn auth_url(&self) -> String {
+        format!("{}/oauth2", self.url)
+    }
+
+    /// Value for `TWITCH_CDN_URL`
+    pub fn cdn_url(&self) -> String {
+        format!("{}/cdn", self.url)
+    }
+
+    /// Revokes every access token issued so far, as Twitch may do before they expire
+    pub fn revoke_tokens(&self) {
+        self.state.lock().unwrap().first_valid_token = TOKENS_ISSUED.load(Ordering::SeqCst);
+    }
+
+    pub fn add_user(&self, login: &str) {
+        let mut state = self.state.lock().unwrap();
+        let id = (state.users.len() + 1).to_string();
+        let login = login.to_lowercase();
+
+        state.users.push(json!({
+            "id": id,
+            "login": login,
+            "display_name": login,
+            "type": "",
+            "broadcaster_type": "affiliate",
+            "description": format!("Bio of {login}"),
+            "profile_image_url": format!("https://static-cdn.jtvnw.net/jtv_user_pictures/{login}-profile_image-300x300.png"),
+            "offline_image_url": "",
+            "view_count": 0,
+            "created_at": "2016-12-14T20:32:28Z",
+        }));
+    }
+
+    pub fn user_id(&self, login: &str) -> Option<String> {
+        let login = login.to_lowercase();
+        self.state
+            .lock()
+            .unwrap()
+            .users
+            .iter()
+            .find(|u| u["login"] == login.as_str())
+            .and_then(|u| u["id"].as_str().map(str::to_string))
+    }
+
+    /// Removes a user, as Twitch does for suspended, banned or deleted accounts
+    pub fn remove_user(&self, login: &str) {
+        let login = login.to_lowercase();
+        self.state
+            .lock()
+            .unwrap()
+            .users
+            .retain(|u| u["login"] != login.as_str());
+    }
+
+    pub fn set_offline_image(&self, login: &str, offline_image_url: &str) {
+        let login = login.to_lowercase();
+        let mut state = self.state.lock().unwrap();
+
+        for user in state.users.iter_mut().filter(|u| u["login"] == login.as_str()) {
+            user["offline_image_url"] = json!(offline_image_url);
+        }
+    }
+
+    /// Changes the login of a user, who keeps their ID
+    pub fn rename_user(&self, login: &str, new_login: &str) {
+        let login = login.to_lowercase();
+        let new_login = new_login.to_lowercase();
+        let mut state = self.state.lock().unwrap();
+
+        for user in state.users.iter_mut().filter(|u| u["login"] == login.as_str()) {
+            user["login"] = json!(new_login);
+            user["display_name"] = json!(new_login);
+        }
+        for stream in state.streams.iter_mut().filter(|s| s["user_login"] == login.as_str()) {
+            stream["user_login"] = json!(new_login);
+            stream["user_name"] = json!(new_login);
+        }
+    }
+
+    pub fn set_live(&self, login: &str, title: &str, viewer_count: u32) {
+        let mut state = self.state.lock().unwrap();
+        let login = login.to_lowercase();
+        let user_id = state
+            .users
+            .iter()
+            .find(|u| u["login"] == login.as_str())
+            .map(|u| u["id"].clone())
+            .unwrap_or_default();
+
+        state.streams.retain(|s| s["user_login"] != login.as_str());
+        state.streams.push(json!({
+            "id": format!("stream-{login}"),
+            "user_id": user_id,
+            "user_login": login,
+            "user_name": login,
+            "game_id": "509658",
+            "game_name": "Just Chatting",
+            "type": "live",
+            "title": title,
+            "tags": ["Français"],
+            "viewer_count": viewer_count,
+            "started_at": "2026-01-01T18:00:00Z",
+            "language": "fr",
+            "thumbnail_url": format!("https://static-cdn.jtvnw.net/previews-ttv/live_user_{login}-{{width}}x{{height}}.jpg"),
+            "is_mature": false,
+        }));
+    }
+
+    pub fn set_offline(&self, login: &str) {
+        let login = login.to_lowercase();
+        self.state
+            .lock()
+            .unwrap()
+            .streams
+            .retain(|s| s["user_login"] != login.as_str());
+    }
+
+    /// Adds a two hour "Just Chatting" segment to the schedule of a user
+    pub fn add_schedule_segment(&self, login: &str, start_time: DateTime<Utc>, title: &str) {
+        let Some(user_id) = self.user_id(login) else {
+            return;
+        };
+        let mut state = self.state.lock().unwrap();
+        let schedule = state.schedules.entry(user_id).or_default();
+
+        schedule.segments.push(json!({
+            "id": format!("segment-{}", schedule.segments.len() + 1),
+            "start_time": start_time,
+            "end_time": start_time + Duration::from_secs(7200),
+            "title": title,
+            "canceled_until": null,
+            "category": { "id": "509658", "name": "Just Chatting" },
+            "is_recurring": false,
+        }));
+        schedule.segments.sort_by_key(|s| date(&s["start_time"]));
+    }
+
+    /// Cancels the segments of a user titled `title`, which Twitch keeps listing
+    pub fn cancel_schedule_segment(&self, login: &str, title: &str) {
+        let Some(user_id) = self.user_id(login) else {
+            return;
+        };
+        let mut state = self.state.lock().unwrap();
+
+        for segment in state
+            .schedules
+            .get_mut(&user_id)
+            .into_iter()
+            .flat_map(|schedule| schedule.segments.iter_mut())
+            .filter(|s| s["title"] == title)
+        {
+            segment["canceled_until"] = segment["end_time"].clone();
+        }
+    }
+
+    /// Marks a user as on vacation between both dates
+    pub fn set_vacation(&self, login: &str, start_time: DateTime<Utc>, end_time: DateTime<Utc>) {
+        let Some(user_id) = self.user_id(login) else {
+            return;
+        };
+
+        self.state
+            .lock()
+            .unwrap()
+            .schedules
+            .entry(user_id)
+            .or_default()
+            .vacation = Some(json!({
+            "start_time": start_time,
+            "end_time": end_time,
+        }));
+    }
+
+    /// Adds a video of a user, `video_type` being `archive` for past broadcasts, `highlight` or `upload`
+    ///
+    /// `duration` is formatted as Twitch does, as "1h2m3s".
+    pub fn add_video(&self, login: &str, video_type: &str, title: &str, duration: &str, created_at: DateTime<Utc>) {
+        let Some(user_id) = self.user_id(login) else {
+            return;
+        };
+        let login = login.to_lowercase();
+        let mut state = self.state.lock().unwrap();
+        let id = (state.videos.len() + 1).to_string();
+
+        state.videos.push(json!({
+            "id": id,
+            "stream_id": null,
+            "user_id": user_id,
+            "user_login": login,
+            "user_name": login,
+            "title": title,
+            "description": "",
+            "created_at": created_at,
+            "published_at": created_at,
+            "url": format!("https://www.twitch.tv/videos/{id}"),
+            "thumbnail_url": format!("https://static-cdn.jtvnw.net/cf_vods/{id}/thumb-%{{width}}x%{{height}}.jpg"),
+            "viewable": "public",
+            "view_count": 0,
+            "language": "fr",
+            "type": video_type,
+            "duration": duration,
+            "muted_segments": null,
+        }));
+    }
+
+    /// Adds a thirty second clip of a user's channel
+    pub fn add_clip(&self, login: &str, title: &str, view_count: u32, created_at: DateTime<Utc>) {
+        let Some(user_id) = self.user_id(login) else {
+            return;
+        };
+        let login = login.to_lowercase();
+        let mut state = self.state.lock().unwrap();
+        let id = format!("Clip{}", state.clips.len() + 1);
+
+        state.clips.push(json!({
+            "id": id,
+            "url": format!("https://clips.twitch.tv/{id}"),
+            "embed_url": format!("https://clips.twitch.tv/embed?clip={id}"),
+            "broadcaster_id": user_id,
+            "broadcaster_name": login,
+            "creator_id": "0",
+            "creator_name": "viewer",
+            "video_id": "",
+            "game_id": "509658",
+            "language": "fr",
+            "title": title,
+            "view_count": view_count,
+            "created_at": created_at,
+            "thumbnail_url": format!("https://clips-media-assets2.twitch.tv/{id}-preview-480x272.jpg"),
+            "duration": 30.0,
+            "vod_offset": null,
+            "is_featured": false,
+        }));
+    }
+
+    /// Leaves `remaining` points in the rate limit bucket, refilled after `refill_in`
+    pub fn drain_rate_limit(&self, remaining: u32, refill_in: Duration) {
+        let refill_at = whole_seconds(Utc::now() + refill_in);
+        self.state.lock().unwrap().bucket = Some((remaining, refill_at));
+    }
+
+    /// Makes paginated endpoints return at most `page_size` entries per page
+    pub fn set_page_size(&self, page_size: usize) {
+        self.state.lock().unwrap().page_size = Some(page_size);
+    }
+
+    /// Adds an enabled webhook subscription, as if created earlier
+    pub fn add_subscription(&self, kind: &str, broadcaster_user_id: &str, callback: &str) {
+        let mut state = self.state.lock().unwrap();
+        let subscription = state.new_subscription(kind, broadcaster_user_id, callback);
+        state.subscriptions.push(subscription);
+    }
+
+    /// Current EventSub subscriptions
+    pub fn subscriptions(&self) -> Vec<Value> {
+        self.state.lock().unwrap().subscriptions.clone()
+    }
+
+    /// Makes the next request to `endpoint` (`token`, `validate`, `users`, `streams`, `schedule`, `videos`, `clips`,
+    /// `subscriptions` or `images`) fail with `status`
+    pub fn fail_next(&self, endpoint: &str, status: StatusCode) {
+        self.state
+            .lock()
+            .unwrap()
+            .failures
+            .entry(endpoint.to_string())
+            .or_default()
+            .push_back(status);
+    }
+
+    /// Number of requests received by `endpoint`
+    pub fn requests(&self, endpoint: &str) -> usize {
+        self.state.lock().unwrap().requests.get(endpoint).copied().unwrap_or(0)
+    }
+
+    /// Paths of the images requested from the CDN, in order
+    pub fn image_requests(&self) -> Vec<String> {
+        self.state.lock().unwrap().images.clone()
+    }
+}
+
+impl MockState {
+    /// Records the request and returns the response to send instead of the regular one, if any
+    fn intercept(&mut self, endpoint: &str, headers: Option<&HeaderMap>) -> Option<Response> {
+        *self.requests.entry(endpoint.to_string()).or_default() += 1;
+
+        if let Some(status) = self.failures.get_mut(endpoint).and_then(VecDeque::pop_front) {
+            return Some(error_response(status));
+        }
+
+        let headers = headers?;
+        let authorized = headers.get("Client-ID").is_some_and(|v| v == MOCK_CLIENT_ID)
+            && headers
+                .get("Authorization")
+                .and_then(|v| v.to_str().ok()?.strip_prefix("Bearer "))
+                .is_some_and(|token| self.is_valid_token(token));
+
+        (!authorized).then(|| error_response(StatusCode::UNAUTHORIZED))
+    }
+
+    fn is_valid_token(&self, token: &str) -> bool {
+        token
+            .strip_prefix(MOCK_ACCESS_TOKEN)
+            .and_then(|n| n.strip_prefix('-')?.parse::<usize>().ok())
+            .is_some_and(|n| n >= self.first_valid_token)
+    }
+
+    /// Enabled webhook subscription with a never reused ID
+    fn new_subscription(&mut self, kind: &str, broadcaster_user_id: &str, callback: &str) -> Value {
+        self.created_subscriptions += 1;
+
+        json!({
+            "id": format!("subscription-{}", self.created_subscriptions),
+            // The callback handshake is not simulated
+            "status": "enabled",
+            "type": kind,
+            "version": "1",
+            "condition": { "broadcaster_user_id": broadcaster_user_id },
+            "created_at": "2026-01-01T18:00:00Z",
+            "transport": { "method": "webhook", "callback": callback },
+            "cost": 1,
+        })
+    }
+}
+
+fn error_response(status: StatusCode) -> Response {
+    let body = json!({
+        "error": status.canonical_reason().unwrap_or_default(),
+        "status": status.as_u16(),
+        "message": "Scripted failure",
+    });
+
+    (status, Json(body)).into_response()
+}
+
+/// Takes a point from the rate limit bucket and reports its state, as Helix does with every response
+async fn rate_limit_headers(State(state): State<SharedState>, mut response: Response) -> Response {
+    let now = Utc::now();
+    let mut state = state.lock().unwrap();
+    let (remaining, refill_at) = match state.bucket {
+        Some((remaining, refill_at)) if refill_at > now => (remaining.saturating_sub(1), refill_at),
+        _ => (RATE_LIMIT - 1, whole_seconds(now + Duration::from_secs(60))),
+    };
+    state.bucket = Some((remaining, refill_at));
+
+    let headers = response.headers_mut();
+    headers.insert("Ratelimit-Limit", HeaderValue::from(RATE_LIMIT));
+    headers.insert("Ratelimit-Remaining", HeaderValue::from(remaining));
+    headers.insert("Ratelimit-Reset", HeaderValue::from(refill_at.timestamp()));
+    response
+}
+
+/// Refill dates are reported in whole seconds
+fn whole_seconds(date: DateTime<Utc>) -> DateTime<Utc> {
+    DateTime::from_timestamp(date.timestamp(), 0).expect("Date is in range")
+}
+
+fn date(value: &Value) -> Option<DateTime<Utc>> {
+    value.as_str()?.parse().ok()
+}
+
+fn query_values<'a>(query: &'a [(String, String)], key: &str) -> Vec<&'a str> {
+    query
+        .iter()
+        .filter(|(k, _)| k == key)
+        .map(|(_, v)| v.as_str())
+        .collect()
+}
+
+async fn token(State(state): State<SharedState>) -> Response {
+    let mut state = state.lock().unwrap();
+    if let Some(response) = state.intercept("token", None) {
+        return response;
+    }
+
+    Json(json!({
+        "access_token": format!("{MOCK_ACCESS_TOKEN}-{}", TOKENS_ISSUED.fetch_add(1, Ordering::SeqCst)),
+        "expires_in": 5_000_000,
+        "token_type": "bearer",
+    }))
+    .into_response()
+}
+
+async fn validate(State(state): State<SharedState>, headers: HeaderMap) -> Response {
+    let mut state = state.lock().unwrap();
+    if let Some(response) = state.intercept("validate", None) {
+        return response;
+    }
+
+    let is_valid = headers
+        .get("Authorization")
+        .and_then(|v| v.to_str().ok()?.strip_prefix("OAuth "))
+        .is_some_and(|token| state.is_valid_token(token));
+    if !is_valid {
+        return error_response(StatusCode::UNAUTHORIZED);
+    }
+
+    Json(json!({
+        "client_id": MOCK_CLIENT_ID,
+        "scopes": [],
+        "expires_in": 5_000_000,
+    }))
+    .into_response()
+}
+
+/// Serves any image, its content being its path
+async fn image(State(state): State<SharedState>, headers: HeaderMap, Path(path): Path<String>) -> Response {
+    let mut state = state.lock().unwrap();
+    if let Some(response) = state.intercept("images", None) {
+        return response;
+    }
+    // The CDN is public, the app credentials have no business there
+    if headers.contains_key("Client-ID") || headers.contains_key("Authorization") {
+        return error_response(StatusCode::BAD_REQUEST);
+    }
+    state.images.push(path.clone());
+
+    format!("image {path}").into_response()
+}
+
+async fn users(
+    State(state): State<SharedState>,
+    headers: HeaderMap,
+    Query(query): Query<Vec<(String, String)>>,
+) -> Response {
+    let mut state = state.lock().unwrap();
+    if let Some(response) = state.intercept("users", Some(&headers)) {
+        return response;
+    }
+
+    let logins = query_values(&query, "login")
+        .into_iter()
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>();
+    let ids = query_values(&query, "id");
+    if logins.len() + ids.len() > 100 {
+        return error_response(StatusCode::BAD_REQUEST);
+    }
+
+    let data = state
+        .users
+        .iter()
+        .filter(|u| logins.iter().any(|l| u["login"] == l.as_str()) || ids.iter().any(|id| u["id"] == *id))
+        .cloned()
+        .collect::<Vec<_>>();
+
+    Json(json!({ "data": data })).into_response()
+}
+
+async fn streams(
+    State(state): State<SharedState>,
+    headers: HeaderMap,
+    Query(query): Query<Vec<(String, String)>>,
+) -> Response {
+    let mut state = state.lock().unwrap();
+    if let Some(response) = state.intercept("streams", Some(&headers)) {
+        return response;
+    }
+
+    let logins = query_values(&query, "user_login")
+        .into_iter()
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>();
+    let ids = query_values(&query, "user_id");
+    if logins.len() + ids.len() > 100 {
+        return error_response(StatusCode::BAD_REQUEST);
+    }
+
+    let matching = state
+        .streams
+        .iter()
+        .filter(|s| logins.iter().any(|l| s["user_login"] == l.as_str()) || ids.iter().any(|id| s["user_id"] == *id))
+        .cloned()
+        .collect::<Vec<_>>();
+
+    // Cursors are plain offsets in the matching streams
+    let first = query_values(&query, "first")
+        .first()
+        .and_then(|f| f.parse().ok())
+        .unwrap_or(20usize);
+    let page_size = state.page_size.map_or(first, |size| size.min(first));
+    let offset = query_values(&query, "after")
+        .first()
+        .and_then(|a| a.parse().ok())
+        .unwrap_or(0usize);
+
+    let data = matching
+        .iter()
+        .skip(offset)
+        .take(page_size)
+        .cloned()
+        .collect::<Vec<_>>();
+    let pagination = if offset + page_size < matching.len() {
+        json!({ "cursor": (offset + page_size).to_string() })
+    } else {
+        json!({})
+    };
+
+    Json(json!({ "data": data, "pagination": pagination })).into_response()
+}
+
+async fn schedule(
+    State(state): State<SharedState>,
+    headers: HeaderMap,
+    Query(query): Query<Vec<(String, String)>>,
+) -> Response {
+    let mut state = state.lock().unwrap();
+    if let Some(response) = state.intercept("schedule", Some(&headers)) {
+        return response;
+    }
+
+    let Some(broadcaster_id) = query_values(&query, "broadcaster_id").first().map(|id| id.to_string()) else {
+        return error_response(StatusCode::BAD_REQUEST);
+    };
+    let Some(user) = state.users.iter().find(|u| u["id"] == broadcaster_id.as_str()) else {
+        return error_response(StatusCode::BAD_REQUEST);
+    };
+    let Some(schedule) = state.schedules.get(&broadcaster_id) else {
+        return error_response(StatusCode::NOT_FOUND);
+    };
+
+    // Segments are listed from now on, up to `first`
+    let first = query_values(&query, "first")
+        .first()
+        .and_then(|f| f.parse().ok())
+        .unwrap_or(20usize);
+    let now = Utc::now();
+    let segments = schedule
+        .segments
+        .iter()
+        .filter(|s| date(&s["end_time"]).is_some_and(|end_time| end_time > now))
+        .take(first)
+        .cloned()
+        .collect::<Vec<_>>();
+
+    Json(json!({
+        "data": {
+            "segments": if segments.is_empty() { Value::Null } else { json!(segments) },
+            "broadcaster_id": broadcaster_id,
+            "broadcaster_name": user["display_name"],
+            "broadcaster_login": user["login"],
+            "vacation": schedule.vacation,
+        },
+        "pagination": {},
+    }))
+    .into_response()
+}
+
+async fn videos(
+    State(state): State<SharedState>,
+    headers: HeaderMap,
+    Query(query): Query<Vec<(String, String)>>,
+) -> Response {
+    let mut state = state.lock().unwrap();
+    if let Some(response) = state.intercept("videos", Some(&headers)) {
+        return response;
+    }
+
+    let Some(user_id) = query_values(&query, "user_id").first().map(|id| id.to_string()) else {
+        return error_response(StatusCode::BAD_REQUEST);
+    };
+    let video_type = query_values(&query, "type").first().map_or("all", |t| *t);
+
+    // Videos are listed the most recent first, up to `first`
+    let first = query_values(&query, "first")
+        .first()
+        .and_then(|f| f.parse().ok())
+        .unwrap_or(20usize);
+    let mut data = state
+        .videos
+        .iter()
+        .filter(|v| v["user_id"] == user_id.as_str() && (video_type == "all" || v["type"] == video_type))
+        .cloned()
+        .collect::<Vec<_>>();
+    data.sort_by_key(|v| std::cmp::Reverse(date(&v["created_at"])));
+    data.truncate(first);
+
+    Json(json!({ "data": data, "pagination": {} })).into_response()
+}
+
+async fn clips(
+    State(state): State<SharedState>,
+    headers: HeaderMap,
+    Query(query): Query<Vec<(String, String)>>,
+) -> Response {
+    let mut state = state.lock().unwrap();
+    if let Some(response) = state.intercept("clips", Some(&headers)) {
+        return response;
+    }
+
+    let Some(broadcaster_id) = query_values(&query, "broadcaster_id").first().map(|id| id.to_string()) else {
+        return error_response(StatusCode::BAD_REQUEST);
+    };
+    let started_at = query_values(&query, "started_at").first().and_then(|d| d.parse().ok());
+    let ended_at = query_values(&query, "ended_at").first().and_then(|d| d.parse().ok());
+
+    // Clips are listed the most viewed first, up to `first`
+    let first = query_values(&query, "first")
+        .first()
+        .and_then(|f| f.parse().ok())
+        .unwrap_or(20usize);
+    let mut data = state
+        .clips
+        .iter()
+        .filter(|c| c["broadcaster_id"] == broadcaster_id.as_str())
+        .filter(|c| {
+            let created_at = date(&c["created_at"]);
+            started_at.is_none_or(|start: DateTime<Utc>| created_at >= Some(start))
+                && ended_at.is_none_or(|end: DateTime<Utc>| created_at.is_some_and(|c| c <= end))
+        })
+        .cloned()
+        .collect::<Vec<_>>();
+    data.sort_by_key(|c| std::cmp::Reverse(c["view_count"].as_u64()));
+    data.truncate(first);
+
+    Json(json!({ "data": data, "pagination": {} })).into_response()
+}
+
+async fn subscriptions(State(state): State<SharedState>, headers: HeaderMap) -> Response {
+    let mut state = state.lock().unwrap();
+    if let Some(response) = state.intercept("subscriptions", Some(&headers)) {
+        return response;
+    }
+
+    Json(json!({
+        "data": state.subscriptions,
+        "total": state.subscriptions.len(),
+        "pagination": {},
+    }))
+    .into_response()
+}
+
+async fn create_subscription(
+    State(state): State<SharedState>,
+    headers: HeaderMap,
+    Json(body): Json<Value>,
+) -> Response {
+    let mut state = state.lock().unwrap();
+    if let Some(response) = state.intercept("subscriptions", Some(&headers)) {
+        return response;
+    }
+
+    let (Some(kind), Some(broadcaster_user_id), Some(callback)) = (
+        body["type"].as_str(),
+        body["condition"]["broadcaster_user_id"].as_str(),
+        body["transport"]["callback"].as_str(),
+    ) else {
+        return error_response(StatusCode::BAD_REQUEST);
+    };
+    if body["transport"]["secret"].as_str().is_none_or(str::is_empty) {
+        return error_response(StatusCode::BAD_REQUEST);
+    }
+    let exists = state
+        .subscriptions
+        .iter()
+        .any(|s| s["type"] == kind && s["condition"]["broadcaster_user_id"] == broadcaster_user_id);
+    if exists {
+        return error_response(StatusCode::CONFLICT);
+    }
+
+    let subscription = state.new_subscription(kind, broadcaster_user_id, callback);
+    state.subscriptions.push(subscription.clone());
+
+    (StatusCode::ACCEPTED, Json(json!({ "data": [subscription] }))).into_response()
+}
+
+async fn delete_subscription(
+    State(state): State<SharedState>,
+    headers: HeaderMap,
+    Query(query): Query<Vec<(String, String)>>,
+) -> Response {
+    let mut state = state.lock().unwrap();
+    if let Some(response) = state.intercept("subscriptions", Some(&headers)) {
+        return response;
+    }
+
+    let Some(id) = query_values(&query, "id").first().map(|id| id.to_string()) else {
+        return error_response(StatusCode::BAD_REQUEST);
+    };
+    let count = state.subscriptions.len();
+    state.subscriptions.retain(|s| s["id"] != id.as_str());
+
+    if state.subscriptions.len() == count {
+        error_response(StatusCode::NOT_FOUND)
+    } else {
+        StatusCode::NO_CONTENT.into_response()
+    }
+}
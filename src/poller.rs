@@ -0,0 +1,217 @@
+use leptos::logging::{error, log, warn};
+use std::{
+    collections::HashMap,
+    sync::{LazyLock, Mutex},
+    time::{Duration, Instant},
+};
+use tokio::{
+    sync::{watch, Notify},
+    time::MissedTickBehavior,
+};
+
+use crate::{
+    fetch_streamers::{load_streamers, sort_streamers, Streamer, StreamerResponse},
+    state::AppState,
+    twitch_error::TwitchError,
+};
+
+/// Latest successful poll, `None` until the first one completes
+static SNAPSHOT: LazyLock<watch::Sender<Option<StreamerResponse>>> = LazyLock::new(|| watch::channel(None).0);
+
+/// Wakes the poller up before its next tick
+static REFRESH: Notify = Notify::const_new();
+
+/// Live statuses pushed by EventSub, keyed by user ID, that Helix has not caught up with yet
+static LIVE_OVERRIDES: LazyLock<Mutex<HashMap<String, (bool, Instant)>>> = LazyLock::new(Default::default);
+
+/// Helix usually reflects a stream going live or offline within a few minutes
+const OVERRIDE_GRACE: Duration = Duration::from_secs(300);
+
+pub fn snapshot() -> Option<StreamerResponse> {
+    SNAPSHOT.borrow().clone()
+}
+
+/// Receiver notified every time the snapshot changes
+pub fn subscribe() -> watch::Receiver<Option<StreamerResponse>> {
+    SNAPSHOT.subscribe()
+}
+
+/// Asks the poller to refresh the snapshot now rather than at its next tick
+pub fn request_refresh() {
+    REFRESH.notify_one();
+}
+
+/// Queries Twitch and replaces the snapshot
+///
+/// On failure the snapshot is kept, flagged as stale.
+pub async fn refresh(app: &AppState) -> Result<StreamerResponse, TwitchError> {
+    let mut response = load_streamers(app).await.inspect_err(|_| mark_stale())?;
+    apply_overrides(&mut response);
+
+    #[cfg(feature = "history")]
+    if let Err(e) = app.history.record(&response).await {
+        error!("Could not record the stream sessions: {e}");
+    }
+
+    SNAPSHOT.send_if_modified(|current| {
+        if let Some(current) = current
+            && current.same_data(&response)
+        {
+            // Nothing worth pushing to the pages
+            current.fetched_at = response.fetched_at;
+            return false;
+        }
+
+        log_unavailable(current.as_ref(), &response);
+        if let Some(previous) = current {
+            log_changes(previous, &response);
+        }
+        *current = Some(response.clone());
+        true
+    });
+
+    Ok(response)
+}
+
+fn mark_stale() {
+    SNAPSHOT.send_if_modified(|current| match current {
+        Some(response) if !response.stale => {
+            response.stale = true;
+            warn!(
+                "serving streamers fetched at {} until Twitch answers again",
+                response.fetched_at
+            );
+            true
+        }
+        _ => false,
+    });
+}
+
+/// Applies a live status change pushed by Twitch, without waiting for the next poll
+///
+/// The status is kept over the polls until Helix agrees with it, or [`OVERRIDE_GRACE`] passes.
+pub fn set_live(user_id: &str, is_live: bool) {
+    LIVE_OVERRIDES
+        .lock()
+        .expect("Live overrides lock is not poisoned")
+        .insert(user_id.to_string(), (is_live, Instant::now()));
+
+    SNAPSHOT.send_if_modified(|current| {
+        let Some(response) = current else {
+            return false;
+        };
+        let Some(streamer) = response
+            .streamers
+            .iter_mut()
+            .find(|s| s.user_id == user_id && s.is_live != is_live)
+        else {
+            return false;
+        };
+
+        override_live(streamer, is_live);
+        log!(
+            "{} went {}",
+            streamer.channel_name,
+            if is_live { "live" } else { "offline" }
+        );
+
+        sort_streamers(&mut response.streamers);
+        true
+    });
+}
+
+/// Keeps the statuses pushed by EventSub over a poll that lags behind them
+fn apply_overrides(response: &mut StreamerResponse) {
+    let mut overrides = LIVE_OVERRIDES.lock().expect("Live overrides lock is not poisoned");
+    overrides.retain(|_, (_, received_at)| received_at.elapsed() < OVERRIDE_GRACE);
+    if overrides.is_empty() {
+        return;
+    }
+
+    for streamer in &mut response.streamers {
+        match overrides.get(&streamer.user_id) {
+            Some(&(is_live, _)) if is_live == streamer.is_live => {
+                overrides.remove(&streamer.user_id);
+            }
+            Some(&(is_live, _)) => override_live(streamer, is_live),
+            None => {}
+        }
+    }
+    sort_streamers(&mut response.streamers);
+}
+
+fn override_live(streamer: &mut Streamer, is_live: bool) {
+    streamer.is_live = is_live;
+    if !is_live {
+        streamer.viewer_count = None;
+        streamer.stream_title = None;
+        streamer.game_name = None;
+        streamer.started_at = None;
+        streamer.language = None;
+        streamer.tags.clear();
+        streamer.is_mature = false;
+        streamer.thumbnail_url = None;
+    }
+}
+
+/// Forgets the statuses pushed by EventSub, trusting the next poll
+pub(crate) fn clear_overrides() {
+    LIVE_OVERRIDES
+        .lock()
+        .expect("Live overrides lock is not poisoned")
+        .clear();
+}
+
+fn log_changes(previous: &StreamerResponse, current: &StreamerResponse) {
+    for streamer in &current.streamers {
+        let was_live = previous
+            .streamers
+            .iter()
+            .find(|s| s.user_id == streamer.user_id)
+            .is_some_and(|s| s.is_live);
+
+        match (was_live, streamer.is_live) {
+            (false, true) => log!("{} went live", streamer.channel_name),
+            (true, false) => log!("{} went offline", streamer.channel_name),
+            _ => {}
+        }
+    }
+}
+
+/// Reports roster members Twitch stopped resolving, every one of them on the first poll
+fn log_unavailable(previous: Option<&StreamerResponse>, current: &StreamerResponse) {
+    let was_unavailable = |login: &str| previous.is_some_and(|p| p.unavailable.iter().any(|u| u.login == login));
+
+    for streamer in current.unavailable.iter().filter(|u| !was_unavailable(&u.login)) {
+        warn!(
+            "{} ({}) is unavailable: {}",
+            streamer.display_name, streamer.login, streamer.reason
+        );
+    }
+    for streamer in previous
+        .iter()
+        .flat_map(|p| &p.unavailable)
+        .filter(|u| !current.unavailable.iter().any(|c| c.login == u.login))
+    {
+        log!("{} ({}) is available again", streamer.display_name, streamer.login);
+    }
+}
+
+/// Polls Twitch forever in the background, starting immediately
+pub fn spawn(app: AppState) {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(app.config.poll_interval);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => {}
+                _ = REFRESH.notified() => ticker.reset(),
+            }
+
+            if let Err(e) = refresh(&app).await {
+                error!("Could not refresh streamers: {e}");
+            }
+        }
+    });
+}
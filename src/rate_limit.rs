@@ -0,0 +1,114 @@
+use axum::Json;
+use chrono::{DateTime, Utc};
+use leptos::logging::warn;
+use reqwest::header::HeaderMap;
+use serde::Serialize;
+use std::{sync::Mutex, time::Duration};
+
+/// Route exposing the rate limit bucket, for the admins holding `ADMIN_TOKEN`
+pub const RATE_LIMIT_PATH: &str = "/api/rate-limit";
+
+/// Requests are delayed until the bucket refills when fewer points than this remain
+const MIN_REMAINING: u32 = 5;
+
+/// Longest wait for the bucket to refill, in case the reset date is off
+const MAX_REFILL_WAIT: Duration = Duration::from_secs(60);
+
+const MAX_RETRIES: u32 = 3;
+const BASE_RETRY_DELAY: Duration = Duration::from_millis(500);
+/// Total time a request may spend waiting between retries
+const RETRY_BUDGET: Duration = Duration::from_secs(30);
+
+static BUCKET: Mutex<Option<Bucket>> = Mutex::new(None);
+
+/// Helix rate limit bucket, as reported by the `Ratelimit-*` headers of the latest response
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Bucket {
+    pub limit: u32,
+    pub remaining: u32,
+    pub reset_at: DateTime<Utc>,
+}
+
+impl Bucket {
+    fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        let header = |name: &str| -> Option<i64> { headers.get(name)?.to_str().ok()?.parse().ok() };
+
+        Some(Self {
+            limit: header("Ratelimit-Limit")?.try_into().ok()?,
+            remaining: header("Ratelimit-Remaining")?.try_into().ok()?,
+            reset_at: DateTime::from_timestamp(header("Ratelimit-Reset")?, 0)?,
+        })
+    }
+
+    /// Time left until the bucket refills
+    fn until_reset(&self) -> Duration {
+        (self.reset_at - Utc::now()).to_std().unwrap_or_default()
+    }
+}
+
+pub fn bucket() -> Option<Bucket> {
+    *BUCKET.lock().expect("Bucket lock is not poisoned")
+}
+
+/// Records the bucket state reported by a response
+pub(crate) fn update(headers: &HeaderMap) {
+    let Some(reported) = Bucket::from_headers(headers) else {
+        return;
+    };
+
+    let mut bucket = BUCKET.lock().expect("Bucket lock is not poisoned");
+    *bucket = Some(match *bucket {
+        // Concurrent responses may arrive out of order, the lowest count being the latest one
+        Some(current) if current.reset_at == reported.reset_at && current.remaining < reported.remaining => current,
+        _ => reported,
+    });
+}
+
+/// Time left until the bucket refills, if it is nearly empty
+pub(crate) fn refill_delay() -> Option<Duration> {
+    bucket()
+        .filter(|bucket| bucket.remaining < MIN_REMAINING)
+        .map(|bucket| bucket.until_reset().min(MAX_REFILL_WAIT))
+        .filter(|delay| !delay.is_zero())
+}
+
+/// Waits for the bucket to refill if it is nearly empty
+pub(crate) async fn wait_for_capacity() {
+    if let Some(delay) = refill_delay() {
+        warn!("Helix rate limit nearly reached, waiting {delay:?} before the next request");
+        tokio::time::sleep(delay).await;
+    }
+}
+
+/// Delays between the retries of a request, growing exponentially with jitter
+#[derive(Debug, Default)]
+pub(crate) struct Backoff {
+    retries: u32,
+    waited: Duration,
+}
+
+impl Backoff {
+    /// Delay before the next retry, at least `min_delay`, or `None` once the retries or the budget are exhausted
+    pub(crate) fn next_delay(&mut self, min_delay: Option<Duration>) -> Option<Duration> {
+        if self.retries >= MAX_RETRIES {
+            return None;
+        }
+
+        let exponential = BASE_RETRY_DELAY * 2u32.pow(self.retries);
+        // Random half of the delay, so that concurrent batches don't retry in lockstep
+        let jitter = rand::random_range(0..=exponential.as_millis() as u64 / 2);
+        let delay = (exponential / 2 + Duration::from_millis(jitter)).max(min_delay.unwrap_or_default());
+        if self.waited + delay > RETRY_BUDGET {
+            return None;
+        }
+
+        self.retries += 1;
+        self.waited += delay;
+        Some(delay)
+    }
+}
+
+/// Current bucket state, `null` until Helix has been queried
+pub async fn rate_limit_status() -> Json<Option<Bucket>> {
+    Json(bucket())
+}
@@ -0,0 +1,228 @@
+use chrono::{DateTime, Utc};
+#[cfg(feature = "ssr")]
+use chrono::{SecondsFormat, TimeDelta};
+#[cfg(feature = "ssr")]
+use futures::future::join_all;
+#[cfg(feature = "ssr")]
+use leptos::logging::warn;
+use leptos::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+#[cfg(feature = "ssr")]
+use std::{cmp::Reverse, sync::LazyLock};
+#[cfg(feature = "ssr")]
+use tokio::sync::Mutex;
+
+use crate::twitch_error::TwitchError;
+#[cfg(feature = "ssr")]
+use crate::{fetch_streamers::Streamer, helix, login_cache::LoginCache, state::AppState, twitch_client::TwitchClient};
+
+/// Reruns of each streamer keyed by user ID, new ones only appearing when a stream ends
+#[cfg(feature = "ssr")]
+static RERUNS_CACHE: LazyLock<Mutex<LoginCache<Vec<Rerun>>>> =
+    LazyLock::new(|| Mutex::new(LoginCache::new(Duration::from_secs(3600))));
+
+/// Past broadcasts played per streamer, the latest ones
+#[cfg(feature = "ssr")]
+const VIDEOS_PER_STREAMER: &str = "1";
+
+/// Clips played per streamer, the most viewed ones
+#[cfg(feature = "ssr")]
+const CLIPS_PER_STREAMER: &str = "3";
+
+/// Clips created this long ago at most
+#[cfg(feature = "ssr")]
+const CLIPS_DAYS: i64 = 30;
+
+/// Past broadcasts are cut after an hour, so that the playlist keeps rotating
+const MAX_VIDEO_PLAY_TIME: Duration = Duration::from_secs(3600);
+
+/// Time for the embed to load before a clip starts playing
+const CLIP_LOAD_TIME: Duration = Duration::from_secs(3);
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
+pub enum RerunKind {
+    /// Past broadcast, kept by Twitch for a few weeks
+    Video,
+    Clip,
+}
+
+/// Past broadcast or clip of a roster member, played while nobody is live
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct Rerun {
+    pub kind: RerunKind,
+    /// Video or clip ID, as expected by the Twitch embeds
+    pub id: String,
+    pub display_name: String,
+    pub channel_name: String,
+    pub title: String,
+    pub duration: Duration,
+    pub created_at: DateTime<Utc>,
+    pub view_count: u32,
+}
+
+impl Rerun {
+    /// URL of the Twitch embed playing this rerun, on the page served from `parent`
+    pub fn embed_url(&self, parent: &str) -> String {
+        match self.kind {
+            RerunKind::Video => format!(
+                "https://player.twitch.tv/?video={}&parent={parent}&autoplay=true",
+                self.id
+            ),
+            RerunKind::Clip => format!(
+                "https://clips.twitch.tv/embed?clip={}&parent={parent}&autoplay=true",
+                self.id
+            ),
+        }
+    }
+
+    /// Time before moving on to the next rerun
+    pub fn play_time(&self) -> Duration {
+        match self.kind {
+            RerunKind::Video => self.duration.min(MAX_VIDEO_PLAY_TIME),
+            RerunKind::Clip => self.duration + CLIP_LOAD_TIME,
+        }
+    }
+}
+
+#[cfg(feature = "ssr")]
+#[derive(Debug, Deserialize)]
+struct VideoData {
+    id: String,
+    title: String,
+    created_at: DateTime<Utc>,
+    view_count: u32,
+    /// As "3h8m33s"
+    duration: String,
+}
+
+#[cfg(feature = "ssr")]
+#[derive(Debug, Deserialize)]
+struct ClipData {
+    id: String,
+    title: String,
+    created_at: DateTime<Utc>,
+    view_count: u32,
+    /// In seconds
+    duration: f64,
+}
+
+/// Parses the durations of the Helix videos, as "3h8m33s"
+#[cfg(feature = "ssr")]
+fn parse_duration(duration: &str) -> Option<Duration> {
+    let mut seconds = 0;
+    let mut number = String::new();
+    for c in duration.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+
+        let unit = match c {
+            'h' => 3600,
+            'm' => 60,
+            's' => 1,
+            _ => return None,
+        };
+        seconds += number.parse::<u64>().ok()? * unit;
+        number.clear();
+    }
+
+    number.is_empty().then(|| Duration::from_secs(seconds))
+}
+
+/// Latest past broadcasts and most viewed recent clips of a streamer
+#[cfg(feature = "ssr")]
+async fn fetch_streamer_reruns(client: &TwitchClient, streamer: &Streamer) -> Result<Vec<Rerun>, TwitchError> {
+    let clips_since = (Utc::now() - TimeDelta::days(CLIPS_DAYS)).to_rfc3339_opts(SecondsFormat::Secs, true);
+    let clips_until = Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true);
+    let video_query = [
+        ("user_id", streamer.user_id.as_str()),
+        ("type", "archive"),
+        ("first", VIDEOS_PER_STREAMER),
+    ];
+    let clip_query = [
+        ("broadcaster_id", streamer.user_id.as_str()),
+        ("started_at", clips_since.as_str()),
+        ("ended_at", clips_until.as_str()),
+        ("first", CLIPS_PER_STREAMER),
+    ];
+
+    let (videos, clips) = tokio::try_join!(
+        helix::get_pages::<VideoData>(client, "videos", &video_query, false),
+        helix::get_pages::<ClipData>(client, "clips", &clip_query, false),
+    )?;
+
+    let videos = videos.into_iter().filter_map(|video| {
+        Some(Rerun {
+            kind: RerunKind::Video,
+            duration: parse_duration(&video.duration)?,
+            id: video.id,
+            display_name: streamer.display_name.clone(),
+            channel_name: streamer.channel_name.clone(),
+            title: video.title,
+            created_at: video.created_at,
+            view_count: video.view_count,
+        })
+    });
+    let clips = clips.into_iter().map(|clip| Rerun {
+        kind: RerunKind::Clip,
+        duration: Duration::from_secs_f64(clip.duration.max(0.0)),
+        id: clip.id,
+        display_name: streamer.display_name.clone(),
+        channel_name: streamer.channel_name.clone(),
+        title: clip.title,
+        created_at: clip.created_at,
+        view_count: clip.view_count,
+    });
+
+    Ok(videos.chain(clips).collect())
+}
+
+/// Playlist of the past broadcasts and clips of `streamers`, the most recent first
+///
+/// A streamer Twitch fails to answer for only shortens the playlist, their reruns being requested again by the next
+/// visitor.
+#[cfg(feature = "ssr")]
+pub async fn load_reruns(client: &TwitchClient, streamers: &[Streamer]) -> Vec<Rerun> {
+    // Held during the requests, so that visitors arriving together while nobody is live share a single fetch
+    let mut cache = RERUNS_CACHE.lock().await;
+    let (cached, missing_ids) = cache.lookup(streamers.iter().map(|s| s.user_id.as_str()));
+
+    let missing = streamers
+        .iter()
+        .filter(|s| missing_ids.contains(&s.user_id))
+        .collect::<Vec<_>>();
+    let fetched = join_all(missing.iter().map(|s| fetch_streamer_reruns(client, s))).await;
+
+    let mut reruns = cached.into_values().flatten().collect::<Vec<_>>();
+    for (streamer, result) in missing.into_iter().zip(fetched) {
+        match result {
+            Ok(streamer_reruns) => {
+                reruns.extend(streamer_reruns.iter().cloned());
+                cache.insert(streamer.user_id.clone(), Some(streamer_reruns));
+            }
+            Err(e) => warn!("Could not fetch the reruns of {}: {e}", streamer.channel_name),
+        }
+    }
+
+    reruns.sort_by_key(|rerun| (Reverse(rerun.created_at), rerun.id.clone()));
+    reruns
+}
+
+/// Forgets the reruns, the next visitor finding nobody live fetching a fresh playlist
+#[cfg(feature = "ssr")]
+pub(crate) async fn clear_cache() {
+    RERUNS_CACHE.lock().await.clear();
+}
+
+/// Reruns of the roster members, only fetched once a visitor finds nobody live
+#[server(GetReruns)]
+pub async fn fetch_reruns() -> Result<Vec<Rerun>, TwitchError> {
+    let app = expect_context::<AppState>();
+    let streamers = crate::poller::snapshot()
+        .map(|response| response.streamers)
+        .unwrap_or_default();
+
+    Ok(load_reruns(&app.twitch, &streamers).await)
+}
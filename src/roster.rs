@@ -0,0 +1,244 @@
+use leptos::logging::{log, warn};
+use serde::Deserialize;
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+    time::{Duration, SystemTime},
+};
+use tokio::signal::unix::{signal, SignalKind};
+
+use crate::{eventsub, fetch_streamers::clear_caches, poller, state::AppState};
+
+/// How often the roster file is checked for modifications
+const WATCH_INTERVAL: Duration = Duration::from_secs(5);
+
+static ROSTER: RwLock<Option<Arc<Roster>>> = RwLock::new(None);
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct RosterEntry {
+    pub display_name: String,
+    pub login: String,
+    /// Twitch user ID, resolved from the login when missing
+    ///
+    /// IDs never change, so streamers are still found after renaming their account.
+    #[serde(default)]
+    pub id: Option<String>,
+    #[serde(default)]
+    pub metadata: HashMap<String, serde_json::Value>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RosterFile {
+    streamers: Vec<RosterEntry>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Roster {
+    pub streamers: Vec<RosterEntry>,
+}
+
+#[derive(Debug)]
+pub enum RosterError {
+    Io(PathBuf, std::io::Error),
+    Parse(PathBuf, String),
+    UnsupportedFormat(PathBuf),
+    Empty,
+    InvalidLogin { display_name: String, login: String },
+    InvalidUserId { display_name: String, id: String },
+    DuplicateLogin(String),
+    DuplicateUserId(String),
+}
+
+impl fmt::Display for RosterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(path, e) => write!(f, "could not read roster file {}: {e}", path.display()),
+            Self::Parse(path, e) => write!(f, "could not parse roster file {}: {e}", path.display()),
+            Self::UnsupportedFormat(path) => write!(
+                f,
+                "unsupported roster file format for {} (expected .toml or .json)",
+                path.display()
+            ),
+            Self::Empty => write!(f, "roster does not contain any streamer"),
+            Self::InvalidLogin { display_name, login } => write!(
+                f,
+                "invalid Twitch login {login:?} for {display_name:?} (expected 1 to 25 letters, digits or underscores, \
+                 not starting with an underscore)"
+            ),
+            Self::InvalidUserId { display_name, id } => {
+                write!(
+                    f,
+                    "invalid Twitch user ID {id:?} for {display_name:?} (expected digits)"
+                )
+            }
+            Self::DuplicateLogin(login) => write!(f, "Twitch login {login:?} appears more than once in the roster"),
+            Self::DuplicateUserId(id) => write!(f, "Twitch user ID {id:?} appears more than once in the roster"),
+        }
+    }
+}
+
+impl std::error::Error for RosterError {}
+
+impl Roster {
+    /// Reads and validates a roster from a `.toml` or `.json` file
+    pub fn load(path: &Path) -> Result<Self, RosterError> {
+        let content = std::fs::read_to_string(path).map_err(|e| RosterError::Io(path.to_path_buf(), e))?;
+
+        let file = match path.extension().and_then(|e| e.to_str()) {
+            Some("toml") => toml::from_str::<RosterFile>(&content).map_err(|e| e.to_string()),
+            Some("json") => serde_json::from_str::<RosterFile>(&content).map_err(|e| e.to_string()),
+            _ => return Err(RosterError::UnsupportedFormat(path.to_path_buf())),
+        }
+        .map_err(|e| RosterError::Parse(path.to_path_buf(), e))?;
+
+        Self::new(file.streamers)
+    }
+
+    /// Validates entries and normalizes logins to lowercase
+    pub fn new(streamers: Vec<RosterEntry>) -> Result<Self, RosterError> {
+        if streamers.is_empty() {
+            return Err(RosterError::Empty);
+        }
+
+        let mut seen = HashSet::new();
+        let mut seen_ids = HashSet::new();
+        let streamers = streamers
+            .into_iter()
+            .map(|mut entry| {
+                if !is_valid_login(&entry.login) {
+                    return Err(RosterError::InvalidLogin {
+                        display_name: entry.display_name,
+                        login: entry.login,
+                    });
+                }
+
+                if let Some(id) = &entry.id {
+                    if !is_valid_user_id(id) {
+                        return Err(RosterError::InvalidUserId {
+                            display_name: entry.display_name,
+                            id: id.clone(),
+                        });
+                    }
+                    // The same streamer listed under their old and new logins
+                    if !seen_ids.insert(id.clone()) {
+                        return Err(RosterError::DuplicateUserId(id.clone()));
+                    }
+                }
+
+                entry.login = entry.login.to_lowercase();
+                if !seen.insert(entry.login.clone()) {
+                    return Err(RosterError::DuplicateLogin(entry.login));
+                }
+
+                Ok(entry)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self { streamers })
+    }
+
+    /// Copies the user IDs resolved for the same logins in `previous`
+    fn inherit_user_ids(&mut self, previous: &Roster) {
+        for entry in self.streamers.iter_mut().filter(|e| e.id.is_none()) {
+            entry.id = previous
+                .streamers
+                .iter()
+                .find(|p| p.login == entry.login)
+                .and_then(|p| p.id.clone());
+        }
+    }
+}
+
+fn is_valid_login(login: &str) -> bool {
+    (1..=25).contains(&login.len())
+        && !login.starts_with('_')
+        && login.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn is_valid_user_id(id: &str) -> bool {
+    !id.is_empty() && id.chars().all(|c| c.is_ascii_digit())
+}
+
+/// Loads the roster at startup, must be called before serving requests
+pub fn init(path: &Path) -> Result<Arc<Roster>, RosterError> {
+    let roster = Arc::new(Roster::load(path)?);
+    set(roster.clone());
+
+    Ok(roster)
+}
+
+/// Currently active roster
+pub fn get() -> Option<Arc<Roster>> {
+    ROSTER.read().expect("Roster lock is not poisoned").clone()
+}
+
+fn set(roster: Arc<Roster>) {
+    *ROSTER.write().expect("Roster lock is not poisoned") = Some(roster);
+}
+
+/// Stores the user IDs resolved for roster logins, so that streamers are looked up by ID from now on
+pub(crate) fn record_user_ids(ids: &HashMap<String, String>) {
+    let mut guard = ROSTER.write().expect("Roster lock is not poisoned");
+    let Some(roster) = guard.as_mut() else {
+        return;
+    };
+
+    for entry in Arc::make_mut(roster).streamers.iter_mut().filter(|e| e.id.is_none()) {
+        entry.id = ids.get(&entry.login).cloned();
+    }
+}
+
+/// Reloads the roster from disk and swaps it in, the active roster is kept if the file is invalid
+pub async fn reload(app: &AppState) -> Result<Arc<Roster>, RosterError> {
+    let mut roster = Roster::load(&app.config.roster_path)?;
+    // Streamers who renamed their account since the roster was written are found by the IDs resolved earlier
+    if let Some(previous) = get() {
+        roster.inherit_user_ids(&previous);
+    }
+    let roster = Arc::new(roster);
+    set(roster.clone());
+    clear_caches().await;
+    poller::request_refresh();
+    eventsub::spawn_sync(app.clone());
+
+    Ok(roster)
+}
+
+/// Reloads the roster whenever its file changes or the process receives SIGHUP
+pub fn spawn_watcher(app: AppState) {
+    // Read before spawning, so that changes made as soon as this returns are not missed
+    let path = app.config.roster_path.clone();
+    let mut last_modified = modified(&path);
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(WATCH_INTERVAL);
+        let mut hangup = signal(SignalKind::hangup()).expect("SIGHUP handler can be installed");
+
+        loop {
+            // Polling the modification date rather than using inotify, which misses events on some bind mounts
+            tokio::select! {
+                _ = interval.tick() => {
+                    let modified = modified(&path);
+                    if modified == last_modified {
+                        continue;
+                    }
+                    last_modified = modified;
+                }
+                _ = hangup.recv() => log!("received SIGHUP, reloading roster"),
+            }
+
+            match reload(&app).await {
+                Ok(roster) => log!("reloaded {} streamers from roster", roster.streamers.len()),
+                Err(e) => warn!("Invalid roster, keeping the previous one: {e}"),
+            }
+        }
+    });
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
@@ -0,0 +1,167 @@
+use chrono::{DateTime, TimeDelta, Utc};
+#[cfg(feature = "ssr")]
+use futures::future::join_all;
+#[cfg(feature = "ssr")]
+use leptos::logging::warn;
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "ssr")]
+use std::{collections::HashMap, sync::LazyLock, time::Duration};
+#[cfg(feature = "ssr")]
+use tokio::sync::Mutex;
+
+use crate::fetch_streamers::Streamer;
+#[cfg(feature = "ssr")]
+use crate::{helix, login_cache::LoginCache, twitch_client::TwitchClient, twitch_error::TwitchError};
+
+/// Schedules change a few times a week at most, keyed by user ID
+#[cfg(feature = "ssr")]
+static SCHEDULES_CACHE: LazyLock<Mutex<LoginCache<Vec<ScheduledStream>>>> =
+    LazyLock::new(|| Mutex::new(LoginCache::new(Duration::from_secs(3600))));
+
+/// Segments requested per schedule, the most Helix returns at once
+#[cfg(feature = "ssr")]
+const SEGMENTS_PER_REQUEST: &str = "25";
+
+/// How far ahead scheduled streams are shown
+pub const SCHEDULE_DAYS: i64 = 7;
+
+/// Stream planned in the Twitch schedule of a roster member
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct ScheduledStream {
+    /// Twitch segment ID, distinct for every occurrence of a recurring segment
+    pub id: String,
+    pub start_time: DateTime<Utc>,
+    /// `None` when the streamer set no end time
+    pub end_time: Option<DateTime<Utc>>,
+    pub title: String,
+    /// Category, as "Just Chatting"
+    pub category: Option<String>,
+}
+
+impl ScheduledStream {
+    /// End of the stream, those without one being shown as lasting an hour
+    pub fn end(&self) -> DateTime<Utc> {
+        self.end_time.unwrap_or(self.start_time + TimeDelta::hours(1))
+    }
+}
+
+#[cfg(feature = "ssr")]
+#[derive(Debug, Deserialize)]
+struct ScheduleData {
+    /// `null` when no segment is left in the requested range
+    #[serde(default)]
+    segments: Option<Vec<SegmentData>>,
+    /// Set while the streamer announced a break, their segments still being listed
+    #[serde(default)]
+    vacation: Option<VacationData>,
+}
+
+#[cfg(feature = "ssr")]
+#[derive(Debug, Deserialize)]
+struct SegmentData {
+    id: String,
+    start_time: DateTime<Utc>,
+    end_time: Option<DateTime<Utc>>,
+    title: String,
+    /// Set when this occurrence of a recurring segment was canceled
+    canceled_until: Option<DateTime<Utc>>,
+    category: Option<CategoryData>,
+}
+
+#[cfg(feature = "ssr")]
+#[derive(Debug, Deserialize)]
+struct CategoryData {
+    name: String,
+}
+
+#[cfg(feature = "ssr")]
+#[derive(Debug, Deserialize)]
+struct VacationData {
+    start_time: DateTime<Utc>,
+    end_time: DateTime<Utc>,
+}
+
+/// Streams of `schedule` starting within the next [`SCHEDULE_DAYS`]
+pub fn upcoming(schedule: &[ScheduledStream], now: DateTime<Utc>) -> Vec<ScheduledStream> {
+    let horizon = now + TimeDelta::days(SCHEDULE_DAYS);
+
+    schedule
+        .iter()
+        .filter(|s| s.start_time > now && s.start_time < horizon)
+        .cloned()
+        .collect()
+}
+
+/// Scheduled streams of the whole roster, the next one first
+pub fn coming_up(streamers: &[Streamer]) -> Vec<(&Streamer, &ScheduledStream)> {
+    let mut streams = streamers
+        .iter()
+        .flat_map(|streamer| streamer.schedule.iter().map(move |stream| (streamer, stream)))
+        .collect::<Vec<_>>();
+    streams.sort_by_key(|(streamer, stream)| (stream.start_time, streamer.display_name.to_lowercase()));
+
+    streams
+}
+
+/// Planned streams of a broadcaster, without the canceled ones and those during their vacation
+#[cfg(feature = "ssr")]
+async fn fetch_schedule(client: &TwitchClient, user_id: &str) -> Result<Vec<ScheduledStream>, TwitchError> {
+    let query = [("broadcaster_id", user_id), ("first", SEGMENTS_PER_REQUEST)];
+    let schedule = match helix::get_one::<ScheduleData>(client, "schedule", &query).await {
+        Ok(schedule) => schedule,
+        // The streamer never set a schedule
+        Err(TwitchError::NotFound(_)) => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+
+    let vacation = schedule.vacation;
+    let on_vacation = |time| {
+        vacation
+            .as_ref()
+            .is_some_and(|v| v.start_time <= time && time < v.end_time)
+    };
+
+    Ok(schedule
+        .segments
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|s| s.canceled_until.is_none() && !on_vacation(s.start_time))
+        .map(|s| ScheduledStream {
+            id: s.id,
+            start_time: s.start_time,
+            end_time: s.end_time,
+            title: s.title,
+            category: s.category.map(|c| c.name),
+        })
+        .collect())
+}
+
+/// Planned streams of the broadcasters in `user_ids`, keyed by user ID
+///
+/// Schedules Twitch fails to return are left out and requested again by the next poll, the streams themselves
+/// being worth showing without them.
+#[cfg(feature = "ssr")]
+pub async fn fetch_schedules(client: &TwitchClient, user_ids: &[String]) -> HashMap<String, Vec<ScheduledStream>> {
+    // Held during the requests, since pages served before the first poll completes refresh alongside the poller
+    let mut cache = SCHEDULES_CACHE.lock().await;
+    let (mut schedules, missing_ids) = cache.lookup(user_ids.iter().map(String::as_str));
+
+    let fetched = join_all(missing_ids.iter().map(|id| fetch_schedule(client, id))).await;
+    for (id, result) in missing_ids.into_iter().zip(fetched) {
+        match result {
+            Ok(schedule) => {
+                schedules.insert(id.clone(), schedule.clone());
+                cache.insert(id, Some(schedule));
+            }
+            Err(e) => warn!("Could not fetch the schedule of user {id}: {e}"),
+        }
+    }
+
+    schedules
+}
+
+/// Forgets the schedules, so that a roster reload shows the latest ones without waiting for the cache to expire
+#[cfg(feature = "ssr")]
+pub(crate) async fn clear_cache() {
+    SCHEDULES_CACHE.lock().await.clear();
+}
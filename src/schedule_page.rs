@@ -0,0 +1,216 @@
+use chrono::{DateTime, Local, NaiveDate, TimeDelta, Utc};
+use leptos::prelude::*;
+use leptos_meta::Title;
+
+use crate::calendar::CALENDAR_PATH;
+use crate::fetch_streamers::{fetch_streamers, Streamer, StreamerResponse};
+use crate::home_page::{use_hydrated, ErrorState};
+use crate::schedule::{coming_up, ScheduledStream, SCHEDULE_DAYS};
+
+/// Height of the day columns, an hour taking 2rem
+const GRID_HEIGHT: &str = "height: 48rem";
+
+/// Scheduled stream placed in the column of a day
+struct GridEntry {
+    streamer: Streamer,
+    stream: ScheduledStream,
+    /// Position and size in the column, in percents of its height
+    top: f64,
+    height: f64,
+    /// Overlapping streams are shown side by side, in lanes splitting the column
+    lane: usize,
+}
+
+/// Start of a day in the visitor's timezone, the earliest one when a DST change repeats midnight
+fn day_start(day: NaiveDate) -> DateTime<Utc> {
+    day.and_hms_opt(0, 0, 0)
+        .and_then(|midnight| midnight.and_local_timezone(Local).earliest())
+        .map_or_else(|| day.and_time(Default::default()).and_utc(), |start| start.to_utc())
+}
+
+/// Streams of the day, and the number of lanes they need
+fn day_entries(day: NaiveDate, streams: &[(Streamer, ScheduledStream)]) -> (Vec<GridEntry>, usize) {
+    let start = day_start(day);
+    let end = day_start(day + TimeDelta::days(1));
+    let length = (end - start).num_minutes() as f64;
+
+    let mut entries = Vec::new();
+    // End of the last stream of each lane
+    let mut lanes = Vec::<DateTime<Utc>>::new();
+    for (streamer, stream) in streams {
+        if stream.start_time >= end || stream.end() <= start {
+            continue;
+        }
+
+        let lane = match lanes.iter().position(|lane_end| *lane_end <= stream.start_time) {
+            Some(lane) => {
+                lanes[lane] = stream.end();
+                lane
+            }
+            None => {
+                lanes.push(stream.end());
+                lanes.len() - 1
+            }
+        };
+        let visible_start = stream.start_time.max(start);
+        let visible_end = stream.end().min(end);
+        entries.push(GridEntry {
+            streamer: streamer.clone(),
+            stream: stream.clone(),
+            top: (visible_start - start).num_minutes() as f64 * 100.0 / length,
+            height: (visible_end - visible_start).num_minutes() as f64 * 100.0 / length,
+            lane,
+        });
+    }
+
+    (entries, lanes.len())
+}
+
+#[component]
+fn GridStream(entry: GridEntry, lanes: usize) -> impl IntoView {
+    let width = 100.0 / lanes as f64;
+    let style = format!(
+        "top: {}%; height: {}%; left: {}%; width: {width}%",
+        entry.top,
+        entry.height,
+        entry.lane as f64 * width,
+    );
+    let times = format!(
+        "{} - {}",
+        entry.stream.start_time.with_timezone(&Local).format("%H:%M"),
+        entry.stream.end().with_timezone(&Local).format("%H:%M"),
+    );
+    let details = [
+        Some(entry.streamer.display_name.clone()),
+        Some(entry.stream.title.clone()).filter(|title| !title.is_empty()),
+        entry.stream.category.clone(),
+        Some(times.clone()),
+    ]
+    .into_iter()
+    .flatten()
+    .collect::<Vec<_>>()
+    .join("\n");
+
+    view! {
+        <a
+            href=format!("/s/{}", entry.streamer.channel_name)
+            class="absolute overflow-hidden rounded-md border border-border bg-primary/80 px-1 text-xs text-primary-foreground hover:bg-primary"
+            style=style
+            title=details
+        >
+            <p class="font-semibold line-clamp-1">{entry.streamer.display_name}</p>
+            <p class="line-clamp-1">{times}</p>
+            <p class="line-clamp-2">{entry.stream.title}</p>
+        </a>
+    }
+}
+
+/// Scheduled streams of the coming week, a column per day in the visitor's timezone
+#[component]
+fn WeekGrid(streamers: Vec<Streamer>) -> impl IntoView {
+    let streams = coming_up(&streamers)
+        .into_iter()
+        .map(|(streamer, stream)| (streamer.clone(), stream.clone()))
+        .collect::<Vec<_>>();
+    let today = Local::now().date_naive();
+
+    view! {
+        <div class="flex flex-row gap-1 my-4">
+            // Hours
+            <div class="flex flex-col w-8 shrink-0">
+                <p class="text-sm invisible">"-"</p>
+                <div class="relative text-xs text-muted-foreground" style=GRID_HEIGHT>
+                    {(0..24u32)
+                        .step_by(3)
+                        .map(|hour| {
+                            view! {
+                                <span class="absolute" style=format!("top: {}%", f64::from(hour) * 100.0 / 24.0)>
+                                    {format!("{hour}h")}
+                                </span>
+                            }
+                        })
+                        .collect_view()}
+                </div>
+            </div>
+            // Days
+            {(0..SCHEDULE_DAYS)
+                .map(|offset| {
+                    let day = today + TimeDelta::days(offset);
+                    let (entries, lanes) = day_entries(day, &streams);
+                    view! {
+                        <div class="flex flex-col flex-1 min-w-0">
+                            <p class="text-sm font-semibold text-center">
+                                {day.format("%a %d/%m").to_string()}
+                            </p>
+                            <div class="relative rounded-md bg-secondary/40" style=GRID_HEIGHT>
+                                {entries
+                                    .into_iter()
+                                    .map(|entry| view! { <GridStream entry lanes /> })
+                                    .collect_view()}
+                            </div>
+                        </div>
+                    }
+                })
+                .collect_view()}
+        </div>
+    }
+}
+
+#[component]
+fn Schedule(response: StreamerResponse) -> impl IntoView {
+    let hydrated = use_hydrated();
+    let calendar_url = format!("webcal://{}{CALENDAR_PATH}", response.base_addr);
+    let streamers = response.streamers;
+
+    view! {
+        <div class="flex flex-wrap gap-4 text-sm">
+            <a href=calendar_url class="hover:underline">
+                "Subscribe"
+            </a>
+            <a href=CALENDAR_PATH class="hover:underline">
+                "Download .ics"
+            </a>
+        </div>
+        {move || {
+            hydrated
+                .get()
+                .then(|| {
+                    if streamers.iter().all(|s| s.schedule.is_empty()) {
+                        view! {
+                            <p class="my-4 text-muted-foreground">"Rien de prévu cette semaine"</p>
+                        }
+                            .into_any()
+                    } else {
+                        view! { <WeekGrid streamers=streamers.clone() /> }.into_any()
+                    }
+                })
+        }}
+    }
+}
+
+#[component]
+pub fn SchedulePage() -> impl IntoView {
+    let streamer_response = Resource::new(|| (), |_| fetch_streamers());
+
+    view! {
+        <Title text="Schedule - WebTV Suspicion" />
+        <div class="px-4">
+            <h1 class="text-2xl font-bold">"Schedule"</h1>
+            <Suspense fallback=move || {
+                view! { <p>"Loading..."</p> }
+            }>
+                {move || {
+                    streamer_response
+                        .get()
+                        .map(|result| match result {
+                            Ok(response) => view! { <Schedule response /> }.into_any(),
+                            Err(error) => {
+                                view! { <ErrorState error on_retry=move || streamer_response.refetch() /> }
+                                    .into_any()
+                            }
+                        })
+                }}
+            </Suspense>
+        </div>
+    }
+}
@@ -0,0 +1,27 @@
+use std::sync::Arc;
+
+#[cfg(feature = "history")]
+use crate::history::History;
+use crate::{config::Config, twitch_client::TwitchClient, twitch_error::TwitchError};
+
+/// Services built at startup, shared by the background tasks and provided to the Leptos app as context
+#[derive(Clone)]
+pub struct AppState {
+    pub config: Arc<Config>,
+    pub twitch: TwitchClient,
+    #[cfg(feature = "history")]
+    pub history: History,
+}
+
+impl AppState {
+    pub fn new(config: Config) -> Result<Self, TwitchError> {
+        Ok(Self {
+            twitch: TwitchClient::new(&config)?,
+            #[cfg(feature = "history")]
+            history: History::open(&config.history_db_path).map_err(|e| {
+                TwitchError::Config(format!("Invalid HISTORY_DB_PATH {:?}: {e}", config.history_db_path))
+            })?,
+            config: Arc::new(config),
+        })
+    }
+}
@@ -0,0 +1,30 @@
+use axum::Json;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::{fetch_streamers::UnavailableStreamer, poller, roster};
+
+/// Route exposing the state of the roster, for the admins holding `ADMIN_TOKEN`
+pub const STATUS_PATH: &str = "/api/status";
+
+#[derive(Debug, Serialize)]
+pub struct Status {
+    /// Number of roster members
+    pub roster_size: usize,
+    /// Roster members Twitch could not resolve, they are missing from the page unless `SHOW_UNAVAILABLE` is set
+    pub unavailable: Vec<UnavailableStreamer>,
+    /// When Twitch was last queried successfully, `null` until the first poll completes
+    pub fetched_at: Option<DateTime<Utc>>,
+    pub stale: bool,
+}
+
+pub async fn status() -> Json<Status> {
+    let snapshot = poller::snapshot();
+
+    Json(Status {
+        roster_size: roster::get().map_or(0, |roster| roster.streamers.len()),
+        unavailable: snapshot.as_ref().map(|s| s.unavailable.clone()).unwrap_or_default(),
+        fetched_at: snapshot.as_ref().map(|s| s.fetched_at),
+        stale: snapshot.is_some_and(|s| s.stale),
+    })
+}
@@ -0,0 +1,326 @@
+use chrono::{Local, TimeDelta, Timelike};
+use leptos::prelude::*;
+use leptos_meta::{Meta, Title};
+use leptos_router::hooks::use_params_map;
+use singlestage::{Avatar, AvatarImage, Badge};
+
+use crate::calendar::CALENDAR_PATH;
+use crate::fetch_streamer_details::{fetch_streamer_details, StreamerDetails};
+use crate::history::StreamSession;
+use crate::home_page::{duration, use_hydrated, AvatarBanner, ErrorState, LocalTime, Uptime};
+use crate::images::{image_url, ImageSize};
+
+/// Sessions listed on the page, older ones only count towards the usual hours
+const RECENT_SESSIONS: usize = 10;
+
+/// Number of sessions live during each hour of the day, in the visitor's timezone
+fn hours_live(sessions: &[StreamSession]) -> [usize; 24] {
+    let mut hours = [0; 24];
+
+    for session in sessions {
+        let ended_at = session.ended_at.unwrap_or(session.last_seen_at);
+        let mut live_hours = [false; 24];
+        let mut time = session.started_at;
+        // Sessions seen by a single poll still count for the hour they started in
+        loop {
+            live_hours[time.with_timezone(&Local).hour() as usize] = true;
+            time += TimeDelta::hours(1);
+            if time >= ended_at || time - session.started_at >= TimeDelta::days(1) {
+                break;
+            }
+        }
+
+        for (count, live) in hours.iter_mut().zip(live_hours) {
+            *count += usize::from(live);
+        }
+    }
+
+    hours
+}
+
+/// Bar chart of the hours the streamer is usually live at, drawn once the visitor's timezone is known
+#[component]
+fn UsualHours(sessions: Vec<StreamSession>) -> impl IntoView {
+    let hydrated = use_hydrated();
+
+    view! {
+        <div class="flex items-end gap-1 h-24 my-4">
+            {move || {
+                hydrated
+                    .get()
+                    .then(|| {
+                        let hours = hours_live(&sessions);
+                        let max = hours.iter().copied().max().unwrap_or(0).max(1);
+                        hours
+                            .into_iter()
+                            .enumerate()
+                            .map(|(hour, count)| {
+                                view! {
+                                    <div
+                                        class="flex-1 rounded-t bg-primary/80"
+                                        style=format!("height: {}%", count * 100 / max)
+                                        title=format!("{hour}h : {count} streams")
+                                    ></div>
+                                }
+                            })
+                            .collect_view()
+                    })
+            }}
+        </div>
+        <div class="flex gap-1 text-xs text-muted-foreground">
+            {(0..24)
+                .map(|hour| {
+                    view! {
+                        <span class="flex-1">{(hour % 3 == 0).then(|| format!("{hour}h"))}</span>
+                    }
+                })
+                .collect_view()}
+        </div>
+    }
+}
+
+#[component]
+fn SessionRow(session: StreamSession) -> impl IntoView {
+    let mut categories = Vec::new();
+    for game_name in session.segments.iter().filter_map(|s| s.game_name.clone()) {
+        if !categories.contains(&game_name) {
+            categories.push(game_name);
+        }
+    }
+    let title = session.segments.last().map(|s| s.title.clone());
+    let length = match session.ended_at {
+        Some(ended_at) => duration(session.started_at, ended_at),
+        None => "LIVE".to_string(),
+    };
+
+    view! {
+        <div class="flex flex-row justify-between gap-4 py-3 border-b border-border">
+            <div class="flex flex-col">
+                <p class="text-md font-semibold line-clamp-1">{title}</p>
+                <p class="text-sm text-muted-foreground line-clamp-1">{categories.join(", ")}</p>
+            </div>
+            <div class="flex flex-col items-end shrink-0 text-sm text-muted-foreground">
+                <p>
+                    <LocalTime time=session.started_at format="%d/%m %H:%M" />
+                </p>
+                <p>
+                    {length}
+                    {session
+                        .peak_viewers
+                        .map(|peak_viewers| format!(", {peak_viewers} viewers max"))}
+                </p>
+            </div>
+        </div>
+    }
+}
+
+#[component]
+fn StreamerProfile(details: StreamerDetails) -> impl IntoView {
+    let StreamerDetails { base_addr, streamer, description, sessions } = details;
+
+    let page_title = format!("{} - WebTV Suspicion", streamer.display_name);
+    let summary = match &streamer.stream_title {
+        Some(stream_title) => format!("En live : {stream_title}"),
+        None => description.clone(),
+    };
+    // Shared links need absolute image URLs
+    let share_image = streamer
+        .thumbnail_url
+        .as_deref()
+        .map(|url| image_url(url, ImageSize::Preview))
+        .or_else(|| streamer.offline_image_url.as_deref().map(|url| image_url(url, ImageSize::Original)))
+        .unwrap_or_else(|| image_url(&streamer.avatar_url, ImageSize::Avatar));
+    let share_image = if share_image.starts_with('/') {
+        format!("https://{base_addr}{share_image}")
+    } else {
+        share_image
+    };
+    let channel_url = format!("https://www.twitch.tv/{}", streamer.channel_name);
+    let calendar_url = format!("{CALENDAR_PATH}?channel={}", streamer.channel_name);
+
+    view! {
+        <Title text=page_title.clone() />
+        <Meta name="description" content=summary.clone() />
+        <Meta property="og:title" content=page_title />
+        <Meta property="og:description" content=summary />
+        <Meta property="og:image" content=share_image />
+        // Stream, or the offline banner
+        <div class="w-full aspect-video overflow-hidden rounded-lg">
+            {if streamer.is_live {
+                view! {
+                    <iframe
+                        src=format!(
+                            "https://player.twitch.tv/?channel={}&parent={base_addr}",
+                            streamer.channel_name,
+                        )
+                        class="w-full h-full"
+                        allowfullscreen="true"
+                    ></iframe>
+                }
+                    .into_any()
+            } else {
+                match streamer.offline_image_url.as_deref() {
+                    Some(banner_url) => {
+                        view! {
+                            <img
+                                src=image_url(banner_url, ImageSize::Original)
+                                alt="Offline Banner"
+                                class="w-full h-full object-cover"
+                            />
+                        }
+                            .into_any()
+                    }
+                    None => view! { <AvatarBanner avatar_url=streamer.avatar_url.clone() /> }.into_any(),
+                }
+            }}
+        </div>
+        // Streamer
+        <div class="flex flex-row items-center gap-4 my-4">
+            <Avatar class="w-16 h-16">
+                <AvatarImage
+                    src=image_url(&streamer.avatar_url, ImageSize::Avatar)
+                    alt=streamer.channel_name.clone()
+                    class="rounded-full w-full h-full object-cover"
+                />
+            </Avatar>
+            <div class="flex flex-col gap-1">
+                <h1 class="text-2xl font-bold">{streamer.display_name}</h1>
+                // Stream status, category, uptime and viewers
+                <div class="flex flex-wrap items-center gap-2">
+                    {if streamer.is_live {
+                        view! {
+                            <Badge class="bg-red-600/60" variant="destructive">
+                                "LIVE"
+                            </Badge>
+                        }
+                    } else {
+                        view! {
+                            <Badge class="bg-secondary/80" variant="secondary">
+                                "OFFLINE"
+                            </Badge>
+                        }
+                    }}
+                    {streamer
+                        .game_name
+                        .map(|game_name| {
+                            view! { <Badge variant="outline">{game_name}</Badge> }
+                        })}
+                    {streamer
+                        .started_at
+                        .map(|started_at| {
+                            view! {
+                                <Badge variant="secondary">
+                                    <Uptime started_at />
+                                </Badge>
+                            }
+                        })}
+                    {streamer
+                        .viewer_count
+                        .map(|viewer_count| {
+                            view! {
+                                <Badge variant="secondary">{viewer_count} " viewers"</Badge>
+                            }
+                        })}
+                </div>
+            </div>
+        </div>
+        <p class="text-md font-semibold">{streamer.stream_title}</p>
+        // Twitch bio
+        <p class="my-2 whitespace-pre-line text-muted-foreground">{description}</p>
+        // Links
+        <div class="flex flex-wrap gap-4 my-4 text-sm">
+            <a href=channel_url.clone() target="_blank" rel="noopener" class="hover:underline">
+                "Twitch"
+            </a>
+            <a href=format!("{channel_url}/videos") target="_blank" rel="noopener" class="hover:underline">
+                "Videos"
+            </a>
+            <a href=format!("{channel_url}/schedule") target="_blank" rel="noopener" class="hover:underline">
+                "Schedule"
+            </a>
+            <a href=calendar_url class="hover:underline">
+                "Calendar"
+            </a>
+            <a href="/" class="hover:underline">
+                "Roster"
+            </a>
+        </div>
+        // Recorded sessions, when the history is enabled
+        {sessions
+            .map(|sessions| {
+                view! {
+                    <div class="my-12">
+                        <h2 class="text-xl font-bold">"Usual hours"</h2>
+                        <UsualHours sessions=sessions.clone() />
+                    </div>
+                    <div class="my-12">
+                        <h2 class="text-xl font-bold">"Recent streams"</h2>
+                        {if sessions.is_empty() {
+                            view! {
+                                <p class="my-4 text-muted-foreground">
+                                    "Pas de stream ces 30 derniers jours"
+                                </p>
+                            }
+                                .into_any()
+                        } else {
+                            sessions
+                                .into_iter()
+                                .take(RECENT_SESSIONS)
+                                .map(|session| view! { <SessionRow session /> })
+                                .collect_view()
+                                .into_any()
+                        }}
+                    </div>
+                }
+            })}
+    }
+}
+
+/// Page of a channel missing from the roster, answered with a 404
+#[component]
+fn UnknownStreamer() -> impl IntoView {
+    #[cfg(feature = "ssr")]
+    if let Some(response) = use_context::<leptos_axum::ResponseOptions>() {
+        response.set_status(axum::http::StatusCode::NOT_FOUND);
+    }
+
+    view! {
+        <Title text="Streamer introuvable - WebTV Suspicion" />
+        <div class="flex flex-col items-center justify-center gap-4 w-full my-12">
+            <p class="text-xl font-semibold">"Ce streamer n'est pas dans le roster"</p>
+            <a href="/" class="hover:underline">
+                "Retour au roster"
+            </a>
+        </div>
+    }
+}
+
+#[component]
+pub fn StreamerPage() -> impl IntoView {
+    let params = use_params_map();
+    let streamer_details = Resource::new(
+        move || params.read().get("channel").unwrap_or_default(),
+        fetch_streamer_details,
+    );
+
+    view! {
+        <div class="px-4">
+            <Suspense fallback=move || {
+                view! { <p>"Loading..."</p> }
+            }>
+                {move || {
+                    streamer_details
+                        .get()
+                        .map(|result| match result {
+                            Ok(Some(details)) => view! { <StreamerProfile details /> }.into_any(),
+                            Ok(None) => view! { <UnknownStreamer /> }.into_any(),
+                            Err(error) => {
+                                view! { <ErrorState error on_retry=move || streamer_details.refetch() /> }
+                                    .into_any()
+                            }
+                        })
+                }}
+            </Suspense>
+        </div>
+    }
+}
@@ -0,0 +1,160 @@
+use leptos::logging::{error, warn};
+use reqwest::{header::HeaderValue, Certificate, Client, IntoUrl, Proxy, RequestBuilder, Response, StatusCode};
+use std::{sync::Arc, time::Duration};
+
+use crate::{
+    config::Config,
+    get_credentials::Credentials,
+    rate_limit::{self, Backoff},
+    twitch_error::TwitchError,
+};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+const READ_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Twitch wants app tokens validated every hour
+const VALIDATION_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// Client for the Twitch APIs, shared by the whole app
+///
+/// Clones share the connection pool and the app access token. [`send`] adds the Client-ID and the access token,
+/// other requests such as the CDN images carry neither.
+///
+/// [`send`]: TwitchClient::send
+#[derive(Clone)]
+pub struct TwitchClient {
+    http: Client,
+    client_id: HeaderValue,
+    api_url: Arc<str>,
+    credentials: Arc<Credentials>,
+}
+
+impl TwitchClient {
+    pub fn new(config: &Config) -> Result<Self, TwitchError> {
+        let client_id = HeaderValue::from_str(&config.twitch_client_id)
+            .map_err(|e| TwitchError::Config(format!("Invalid TWITCH_CLIENT_ID: {e}")))?;
+
+        let mut builder = Client::builder()
+            .user_agent(concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION")))
+            .connect_timeout(CONNECT_TIMEOUT)
+            .read_timeout(READ_TIMEOUT);
+        if let Some(proxy_url) = &config.twitch_proxy_url {
+            let proxy =
+                Proxy::all(proxy_url).map_err(|e| TwitchError::Config(format!("Invalid TWITCH_PROXY_URL: {e}")))?;
+            builder = builder.proxy(proxy);
+        }
+        if let Some(ca_path) = &config.twitch_ca_cert {
+            let certificates = std::fs::read(ca_path)
+                .map_err(|e| e.to_string())
+                .and_then(|pem| Certificate::from_pem_bundle(&pem).map_err(|e| e.to_string()))
+                .map_err(|e| TwitchError::Config(format!("Invalid TWITCH_CA_CERT {ca_path:?}: {e}")))?;
+            builder = builder.tls_certs_merge(certificates);
+        }
+
+        Ok(Self {
+            http: builder.build()?,
+            client_id,
+            api_url: config.twitch_api_url.as_str().into(),
+            credentials: Arc::new(Credentials::new(
+                config.twitch_auth_url.clone(),
+                config.twitch_client_id.clone(),
+                config.twitch_client_secret.clone(),
+                config.twitch_token_path.clone(),
+            )),
+        })
+    }
+
+    /// Base URL of the Helix API
+    pub fn api_url(&self) -> &str {
+        &self.api_url
+    }
+
+    pub fn get(&self, url: impl IntoUrl) -> RequestBuilder {
+        self.http.get(url)
+    }
+
+    pub fn post(&self, url: impl IntoUrl) -> RequestBuilder {
+        self.http.post(url)
+    }
+
+    pub fn delete(&self, url: impl IntoUrl) -> RequestBuilder {
+        self.http.delete(url)
+    }
+
+    /// Sends a request with the Client-ID and the app access token
+    ///
+    /// Requests wait for the rate limit bucket to refill when it is nearly empty, and are retried with backoff when
+    /// Twitch answers 429 or 5xx. Twitch may also revoke a token before it expires, in which case a new one is
+    /// requested and the request sent again once.
+    pub async fn send(&self, request: RequestBuilder) -> Result<Response, TwitchError> {
+        let mut backoff = Backoff::default();
+        let mut token_renewed = false;
+
+        loop {
+            let attempt = request.try_clone().expect("Twitch requests have no streaming body");
+            rate_limit::wait_for_capacity().await;
+            let access_token = self.credentials.get_access_token(&self.http).await?;
+
+            let response = match attempt
+                .header("Client-ID", self.client_id.clone())
+                .bearer_auth(&access_token)
+                .send()
+                .await
+            {
+                Ok(response) => response,
+                Err(e) if e.is_connect() || e.is_timeout() => match backoff.next_delay(None) {
+                    Some(delay) => {
+                        warn!("Could not reach Twitch, retrying in {delay:?}: {e}");
+                        tokio::time::sleep(delay).await;
+                        continue;
+                    }
+                    None => return Err(e.into()),
+                },
+                Err(e) => return Err(e.into()),
+            };
+            rate_limit::update(response.headers());
+
+            let status = response.status();
+            if status == StatusCode::UNAUTHORIZED && !token_renewed {
+                warn!("Twitch rejected the access token, requesting a new one");
+                self.credentials.invalidate_access_token(&access_token).await;
+                token_renewed = true;
+                continue;
+            }
+            if status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error() {
+                // Retrying a 429 before the bucket refills would fail again
+                let min_delay = (status == StatusCode::TOO_MANY_REQUESTS)
+                    .then(rate_limit::refill_delay)
+                    .flatten();
+                if let Some(delay) = backoff.next_delay(min_delay) {
+                    warn!("Twitch answered {status}, retrying in {delay:?}");
+                    tokio::time::sleep(delay).await;
+                    continue;
+                }
+            }
+
+            return Ok(response.error_for_status()?);
+        }
+    }
+
+    /// Checks the access token against `oauth2/validate`, forgetting it if Twitch no longer accepts it
+    pub async fn validate_access_token(&self) -> Result<(), TwitchError> {
+        self.credentials.validate_access_token(&self.http).await
+    }
+
+    /// Validates the access token every hour, as Twitch requires from applications
+    pub fn spawn_validator(&self) {
+        let client = self.clone();
+
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(VALIDATION_INTERVAL);
+
+            loop {
+                interval.tick().await;
+                if let Err(e) = client.validate_access_token().await {
+                    error!("Could not validate the access token: {e}");
+                }
+            }
+        });
+    }
+}
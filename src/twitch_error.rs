@@ -0,0 +1,79 @@
+use leptos::{prelude::*, server_fn::codec::JsonEncoding};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Failure to get the roster state from Twitch
+///
+/// Every variant carries details meant for the server logs, the UI only shows [`TwitchError::user_message`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum TwitchError {
+    /// Missing or invalid server setting, such as `TWITCH_CLIENT_ID`
+    Config(String),
+    /// Twitch refused our credentials
+    AuthRejected(String),
+    /// Too many requests sent to Twitch
+    RateLimited(String),
+    /// Twitch could not be reached, or answered with a server error
+    Unavailable(String),
+    /// Twitch has nothing at this address, such as the schedule of a channel which never set one
+    NotFound(String),
+    /// Twitch answered with something we don't understand
+    Decode(String),
+    /// Failure outside of Twitch, such as the server function call itself
+    Server(String),
+}
+
+impl TwitchError {
+    /// Explanation shown to visitors
+    pub fn user_message(&self) -> &'static str {
+        match self {
+            Self::Config(_) => "Le serveur est mal configuré, préviens l'admin",
+            Self::AuthRejected(_) => "Twitch refuse nos identifiants, préviens l'admin",
+            Self::RateLimited(_) => "Trop de requêtes envoyées à Twitch, réessaie dans une minute",
+            Self::Unavailable(_) => "Twitch ne répond pas, réessaie dans un instant",
+            Self::NotFound(_) => "Introuvable sur Twitch",
+            Self::Decode(_) => "Twitch a répondu n'importe quoi, réessaie dans un instant",
+            Self::Server(_) => "Impossible de joindre le serveur, réessaie dans un instant",
+        }
+    }
+}
+
+impl fmt::Display for TwitchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Config(details) => write!(f, "invalid configuration: {details}"),
+            Self::AuthRejected(details) => write!(f, "Twitch rejected our credentials: {details}"),
+            Self::RateLimited(details) => write!(f, "rate limited by Twitch: {details}"),
+            Self::Unavailable(details) => write!(f, "Twitch is unavailable: {details}"),
+            Self::NotFound(details) => write!(f, "not found on Twitch: {details}"),
+            Self::Decode(details) => write!(f, "unexpected response from Twitch: {details}"),
+            Self::Server(details) => write!(f, "{details}"),
+        }
+    }
+}
+
+impl std::error::Error for TwitchError {}
+
+impl FromServerFnError for TwitchError {
+    type Encoder = JsonEncoding;
+
+    fn from_server_fn_error(value: ServerFnErrorErr) -> Self {
+        Self::Server(value.to_string())
+    }
+}
+
+#[cfg(feature = "ssr")]
+impl From<reqwest::Error> for TwitchError {
+    fn from(e: reqwest::Error) -> Self {
+        use reqwest::StatusCode;
+
+        match e.status() {
+            Some(StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN) => Self::AuthRejected(e.to_string()),
+            Some(StatusCode::TOO_MANY_REQUESTS) => Self::RateLimited(e.to_string()),
+            Some(StatusCode::NOT_FOUND) => Self::NotFound(e.to_string()),
+            _ if e.is_decode() => Self::Decode(e.to_string()),
+            _ if e.is_builder() => Self::Server(e.to_string()),
+            _ => Self::Unavailable(e.to_string()),
+        }
+    }
+}
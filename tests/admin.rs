@@ -0,0 +1,87 @@
+mod common;
+
+use axum::{http::StatusCode, middleware, routing::get, Router};
+use common::{app, serve, setup};
+use webtv::{
+    admin::require_admin,
+    poller,
+    rate_limit::{rate_limit_status, RATE_LIMIT_PATH},
+    state::AppState,
+    status::{status, STATUS_PATH},
+};
+
+const ADMIN_TOKEN: &str = "admin-test-token";
+
+/// Starts a server exposing the admin endpoints, returning its URL
+async fn start_admin(app: AppState) -> String {
+    let router = Router::new()
+        .route(STATUS_PATH, get(status))
+        .route(RATE_LIMIT_PATH, get(rate_limit_status))
+        .layer(middleware::from_fn_with_state(app, require_admin));
+
+    serve(router).await
+}
+
+async fn get_as(url: &str, token: Option<&str>) -> reqwest::Response {
+    let request = reqwest::Client::new().get(url);
+    match token {
+        Some(token) => request.bearer_auth(token),
+        None => request,
+    }
+    .send()
+    .await
+    .unwrap()
+}
+
+#[tokio::test]
+async fn disables_the_admin_endpoints_without_a_token() {
+    let (_guard, _twitch, app) = setup(&[("Shokk", "shokk")]).await;
+    let server = start_admin(app).await;
+
+    for path in [STATUS_PATH, RATE_LIMIT_PATH] {
+        let response = get_as(&format!("{server}{path}"), Some("")).await;
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND, "{path}");
+    }
+}
+
+#[tokio::test]
+async fn disables_the_admin_endpoints_with_an_empty_token() {
+    let (_guard, _twitch, _app) = setup(&[("Shokk", "shokk")]).await;
+    // SAFETY: the setup lock is held
+    unsafe { std::env::set_var("ADMIN_TOKEN", "") };
+    let server = start_admin(app()).await;
+
+    for path in [STATUS_PATH, RATE_LIMIT_PATH] {
+        let response = get_as(&format!("{server}{path}"), None).await;
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND, "{path}");
+    }
+}
+
+#[tokio::test]
+async fn requires_the_admin_token() {
+    let (_guard, _twitch, _app) = setup(&[("Shokk", "shokk"), ("Ghost", "ghost")]).await;
+    // SAFETY: the setup lock is held
+    unsafe { std::env::set_var("ADMIN_TOKEN", ADMIN_TOKEN) };
+    let app = app();
+    poller::refresh(&app).await.unwrap();
+    let server = start_admin(app).await;
+
+    for path in [STATUS_PATH, RATE_LIMIT_PATH] {
+        let url = format!("{server}{path}");
+        assert_eq!(get_as(&url, None).await.status(), StatusCode::UNAUTHORIZED, "{path}");
+        assert_eq!(
+            get_as(&url, Some("wrong")).await.status(),
+            StatusCode::UNAUTHORIZED,
+            "{path}"
+        );
+    }
+
+    let response = get_as(&format!("{server}{STATUS_PATH}"), Some(ADMIN_TOKEN)).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let status = response.json::<serde_json::Value>().await.unwrap();
+    assert_eq!(status["roster_size"], 2);
+    let response = get_as(&format!("{server}{RATE_LIMIT_PATH}"), Some(ADMIN_TOKEN)).await;
+    assert_eq!(response.status(), StatusCode::OK);
+}
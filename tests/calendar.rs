@@ -0,0 +1,112 @@
+mod common;
+
+use axum::{
+    http::{header::CONTENT_TYPE, StatusCode},
+    routing::get,
+    Router,
+};
+use chrono::{DateTime, TimeDelta, Utc};
+use common::{serve, setup};
+use webtv::{
+    calendar::{calendar, CALENDAR_PATH},
+    poller,
+};
+
+/// Starts a server serving the calendar feed, returning its URL
+async fn start_feed() -> String {
+    let router = Router::new().route(CALENDAR_PATH, get(calendar));
+
+    format!("{}{CALENDAR_PATH}", serve(router).await)
+}
+
+/// Tomorrow at 18:00 UTC, plus `hours`
+fn tomorrow(hours: i64) -> DateTime<Utc> {
+    let tomorrow = Utc::now().date_naive() + TimeDelta::days(1);
+
+    tomorrow.and_hms_opt(18, 0, 0).unwrap().and_utc() + TimeDelta::hours(hours)
+}
+
+/// Content lines of a calendar, unfolded
+fn unfold(calendar: &str) -> Vec<String> {
+    calendar
+        .replace("\r\n ", "")
+        .split_terminator("\r\n")
+        .map(str::to_string)
+        .collect()
+}
+
+#[tokio::test]
+async fn serves_the_roster_schedule() {
+    let (_guard, twitch, app) = setup(&[("Shokk", "shokk"), ("Other", "other")]).await;
+    twitch.add_schedule_segment("shokk", tomorrow(2), "Raid night");
+    twitch.add_schedule_segment("other", tomorrow(0), "Chill, talk; and more");
+    poller::refresh(&app).await.unwrap();
+    let feed = start_feed().await;
+
+    let response = reqwest::get(&feed).await.unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()[CONTENT_TYPE], "text/calendar; charset=utf-8");
+    let lines = unfold(&response.text().await.unwrap());
+    assert_eq!(lines.first().unwrap(), "BEGIN:VCALENDAR");
+    assert_eq!(lines.last().unwrap(), "END:VCALENDAR");
+    assert!(lines.contains(&"X-WR-CALNAME:WebTV Suspicion".to_string()));
+    // Events are ordered by start, and their text escaped
+    let summaries = lines
+        .iter()
+        .filter_map(|line| line.strip_prefix("SUMMARY:"))
+        .collect::<Vec<_>>();
+    assert_eq!(summaries, ["Other - Chill\\, talk\\; and more", "Shokk - Raid night"]);
+    let start = tomorrow(2).format("%Y%m%dT%H%M%SZ");
+    let end = tomorrow(4).format("%Y%m%dT%H%M%SZ");
+    assert!(lines.contains(&format!("DTSTART:{start}")));
+    assert!(lines.contains(&format!("DTEND:{end}")));
+    assert!(lines.contains(&"DESCRIPTION:Just Chatting\\nhttps://www.twitch.tv/shokk".to_string()));
+    assert!(lines
+        .iter()
+        .any(|line| line.starts_with("UID:") && line.ends_with("@webtv.test")));
+}
+
+#[tokio::test]
+async fn serves_a_feed_per_streamer() {
+    let (_guard, twitch, app) = setup(&[("Shokk", "shokk"), ("Other", "other")]).await;
+    twitch.add_schedule_segment("shokk", tomorrow(2), "Raid night");
+    twitch.add_schedule_segment("other", tomorrow(0), "Chill");
+    poller::refresh(&app).await.unwrap();
+    let feed = start_feed().await;
+
+    let calendar = reqwest::get(format!("{feed}?channel=Shokk"))
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+
+    let lines = unfold(&calendar);
+    assert!(lines.contains(&"X-WR-CALNAME:Shokk - WebTV Suspicion".to_string()));
+    let summaries = lines
+        .iter()
+        .filter_map(|line| line.strip_prefix("SUMMARY:"))
+        .collect::<Vec<_>>();
+    assert_eq!(summaries, ["Shokk - Raid night"]);
+    let unknown = reqwest::get(format!("{feed}?channel=nobody")).await.unwrap();
+    assert_eq!(unknown.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn folds_long_lines() {
+    let (_guard, twitch, app) = setup(&[("Shokk", "shokk")]).await;
+    let title = "Très longue soirée de raids, ".repeat(6);
+    twitch.add_schedule_segment("shokk", tomorrow(2), &title);
+    poller::refresh(&app).await.unwrap();
+    let feed = start_feed().await;
+
+    let calendar = reqwest::get(&feed).await.unwrap().text().await.unwrap();
+
+    assert!(calendar.split("\r\n").all(|line| line.len() <= 75));
+    let summary = unfold(&calendar)
+        .into_iter()
+        .find_map(|line| line.strip_prefix("SUMMARY:").map(str::to_string))
+        .unwrap();
+    assert_eq!(summary, format!("Shokk - {}", title.replace(',', "\\,")));
+}
@@ -0,0 +1,83 @@
+use axum::{http::StatusCode, Router};
+use tokio::sync::{Mutex, MutexGuard};
+use webtv::{
+    config::Config,
+    fetch_streamers::clear_caches,
+    mock_twitch::{MockTwitch, MOCK_CLIENT_ID, MOCK_CLIENT_SECRET},
+    roster,
+    state::AppState,
+};
+
+/// Tests share the process environment, the active roster and the Twitch caches, so they run one at a time
+static LOCK: Mutex<()> = Mutex::const_new(());
+
+/// Starts a mock Twitch server knowing every roster member, and points the app to it
+pub async fn setup(streamers: &[(&str, &str)]) -> (MutexGuard<'static, ()>, MockTwitch, AppState) {
+    let guard = LOCK.lock().await;
+    let twitch = MockTwitch::start().await;
+
+    let roster_path = std::env::temp_dir().join(format!("webtv-roster-{}.toml", std::process::id()));
+    let roster_content = streamers
+        .iter()
+        .map(|(display_name, login)| {
+            format!("[[streamers]]\ndisplay_name = \"{display_name}\"\nlogin = \"{login}\"\n\n")
+        })
+        .collect::<String>();
+    std::fs::write(&roster_path, roster_content).unwrap();
+    let image_cache_dir = std::env::temp_dir().join(format!("webtv-images-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&image_cache_dir);
+    let history_db_path = std::env::temp_dir().join(format!("webtv-history-{}.db", std::process::id()));
+    for suffix in ["", "-wal", "-shm"] {
+        let mut path = history_db_path.clone().into_os_string();
+        path.push(suffix);
+        let _ = std::fs::remove_file(path);
+    }
+
+    // SAFETY: tests holding LOCK are the only ones touching the environment
+    unsafe {
+        std::env::set_var("ROSTER_PATH", &roster_path);
+        std::env::set_var("TWITCH_API_URL", twitch.api_url());
+        std::env::set_var("TWITCH_AUTH_URL", twitch.auth_url());
+        std::env::set_var("TWITCH_CDN_URL", twitch.cdn_url());
+        std::env::set_var("IMAGE_CACHE_DIR", &image_cache_dir);
+        std::env::set_var("HISTORY_DB_PATH", &history_db_path);
+        std::env::set_var("TWITCH_CLIENT_ID", MOCK_CLIENT_ID);
+        std::env::set_var("TWITCH_CLIENT_SECRET", MOCK_CLIENT_SECRET);
+        std::env::set_var("BASE_ADDR", "webtv.test");
+        std::env::remove_var("TWITCH_TOKEN_PATH");
+        std::env::remove_var("CONFIG_PATH");
+        std::env::remove_var("ADMIN_TOKEN");
+    }
+    let app = app();
+    roster::init(&app.config.roster_path).unwrap();
+    clear_caches().await;
+
+    for (_, login) in streamers {
+        twitch.add_user(login);
+    }
+
+    (guard, twitch, app)
+}
+
+/// App services configured from the environment set by [`setup`]
+pub fn app() -> AppState {
+    AppState::new(Config::load().unwrap()).unwrap()
+}
+
+/// Serves `router` on a free local port, returning its base URL
+#[allow(dead_code, reason = "not every test crate starts a server")]
+pub async fn serve(router: Router) -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, router).await });
+
+    url
+}
+
+/// Makes `endpoint` fail more times than the client retries
+#[allow(dead_code, reason = "not every test crate simulates Twitch outages")]
+pub fn fail_persistently(twitch: &MockTwitch, endpoint: &str, status: StatusCode) {
+    for _ in 0..4 {
+        twitch.fail_next(endpoint, status);
+    }
+}
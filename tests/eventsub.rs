@@ -0,0 +1,285 @@
+mod common;
+
+use axum::{http::StatusCode, routing::post, Router};
+use chrono::{SecondsFormat, TimeDelta, Utc};
+use common::{app, serve, setup};
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use webtv::{
+    eventsub::{eventsub_webhook, signature, sync_subscriptions, EVENTSUB_PATH},
+    poller,
+    state::AppState,
+};
+
+const SECRET: &str = "eventsub-test-secret";
+const CALLBACK: &str = "https://webtv.test/eventsub";
+
+/// Message IDs must be unique, handled IDs are remembered by the app
+static NEXT_MESSAGE_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// Enables EventSub and starts a server receiving webhooks, returning its URL and the app it belongs to
+async fn start_webhook() -> (String, AppState) {
+    // SAFETY: tests holding the setup lock are the only ones touching the environment
+    unsafe {
+        std::env::set_var("TWITCH_EVENTSUB_SECRET", SECRET);
+        std::env::set_var("TWITCH_EVENTSUB_CALLBACK", CALLBACK);
+    }
+
+    let app = app();
+
+    let router = Router::new().route(EVENTSUB_PATH, post(eventsub_webhook).with_state(app.clone()));
+    let url = format!("{}{EVENTSUB_PATH}", serve(router).await);
+
+    (url, app)
+}
+
+fn message_id() -> String {
+    format!("message-{}", NEXT_MESSAGE_ID.fetch_add(1, Ordering::Relaxed))
+}
+
+fn subscription(kind: &str, broadcaster_user_id: &str) -> Value {
+    json!({
+        "id": "f1c2a387-161a-49f9-a165-0f21d7a4e1c4",
+        "status": "enabled",
+        "type": kind,
+        "version": "1",
+        "cost": 1,
+        "condition": { "broadcaster_user_id": broadcaster_user_id },
+        "transport": { "method": "webhook", "callback": CALLBACK },
+        "created_at": "2026-01-01T18:00:00Z",
+    })
+}
+
+fn stream_event(kind: &str, broadcaster_user_id: &str, login: &str) -> Value {
+    json!({
+        "subscription": subscription(kind, broadcaster_user_id),
+        "event": {
+            "id": "9001",
+            "broadcaster_user_id": broadcaster_user_id,
+            "broadcaster_user_login": login,
+            "broadcaster_user_name": login,
+            "type": "live",
+            "started_at": "2026-01-01T18:00:00Z",
+        },
+    })
+}
+
+/// Sends a message as Twitch would, signed with `secret` and sent `age` ago
+async fn deliver(
+    url: &str,
+    message_type: &str,
+    message_id: &str,
+    body: &Value,
+    secret: &str,
+    age: TimeDelta,
+) -> reqwest::Response {
+    let body = body.to_string();
+    let timestamp = (Utc::now() - age).to_rfc3339_opts(SecondsFormat::Nanos, true);
+
+    reqwest::Client::new()
+        .post(url)
+        .header("Content-Type", "application/json")
+        .header("Twitch-Eventsub-Message-Id", message_id)
+        .header("Twitch-Eventsub-Message-Retry", "0")
+        .header("Twitch-Eventsub-Message-Type", message_type)
+        .header("Twitch-Eventsub-Message-Timestamp", &timestamp)
+        .header(
+            "Twitch-Eventsub-Message-Signature",
+            signature(secret, message_id, &timestamp, body.as_bytes()),
+        )
+        .header("Twitch-Eventsub-Subscription-Type", "stream.online")
+        .header("Twitch-Eventsub-Subscription-Version", "1")
+        .body(body)
+        .send()
+        .await
+        .unwrap()
+}
+
+async fn notify(url: &str, body: &Value) -> reqwest::Response {
+    deliver(url, "notification", &message_id(), body, SECRET, TimeDelta::zero()).await
+}
+
+#[tokio::test]
+async fn answers_the_callback_challenge() {
+    let (_guard, _twitch, _app) = setup(&[("Shokk", "shokkfamedslayer")]).await;
+    let (url, _app) = start_webhook().await;
+    let body = json!({
+        "challenge": "pogchamp-kappa-360noscope-vohiyo",
+        "subscription": subscription("stream.online", "1"),
+    });
+
+    let response = deliver(
+        &url,
+        "webhook_callback_verification",
+        &message_id(),
+        &body,
+        SECRET,
+        TimeDelta::zero(),
+    )
+    .await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.headers()["Content-Type"]
+        .to_str()
+        .unwrap()
+        .starts_with("text/plain"));
+    assert_eq!(response.text().await.unwrap(), "pogchamp-kappa-360noscope-vohiyo");
+}
+
+#[tokio::test]
+async fn rejects_invalid_signatures() {
+    let (_guard, _twitch, _app) = setup(&[("Shokk", "shokkfamedslayer")]).await;
+    let (url, _app) = start_webhook().await;
+    let body = stream_event("stream.online", "1", "shokkfamedslayer");
+
+    let response = deliver(
+        &url,
+        "notification",
+        &message_id(),
+        &body,
+        "wrong-secret",
+        TimeDelta::zero(),
+    )
+    .await;
+
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn rejects_stale_messages() {
+    let (_guard, _twitch, _app) = setup(&[("Shokk", "shokkfamedslayer")]).await;
+    let (url, _app) = start_webhook().await;
+    let body = stream_event("stream.online", "1", "shokkfamedslayer");
+
+    let response = deliver(
+        &url,
+        "notification",
+        &message_id(),
+        &body,
+        SECRET,
+        TimeDelta::minutes(11),
+    )
+    .await;
+
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn applies_stream_events_without_polling() {
+    let (_guard, twitch, _app) = setup(&[("Shokk", "shokkfamedslayer")]).await;
+    let (url, app) = start_webhook().await;
+    let user_id = twitch.user_id("shokkfamedslayer").unwrap();
+    twitch.set_live("shokkfamedslayer", "Raid night", 42);
+    poller::refresh(&app).await.unwrap();
+
+    let response = notify(&url, &stream_event("stream.offline", &user_id, "shokkfamedslayer")).await;
+
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    let streamer = &poller::snapshot().unwrap().streamers[0];
+    assert!(!streamer.is_live);
+    assert_eq!(streamer.viewer_count, None);
+    assert_eq!(streamer.stream_title, None);
+
+    notify(&url, &stream_event("stream.online", &user_id, "shokkfamedslayer")).await;
+
+    assert!(poller::snapshot().unwrap().streamers[0].is_live);
+    assert_eq!(twitch.requests("streams"), 1);
+}
+
+#[tokio::test]
+async fn keeps_stream_events_until_helix_catches_up() {
+    let (_guard, twitch, _app) = setup(&[("Shokk", "shokkfamedslayer")]).await;
+    let (url, app) = start_webhook().await;
+    let user_id = twitch.user_id("shokkfamedslayer").unwrap();
+    twitch.set_live("shokkfamedslayer", "Raid night", 42);
+    poller::refresh(&app).await.unwrap();
+    notify(&url, &stream_event("stream.offline", &user_id, "shokkfamedslayer")).await;
+
+    // Helix still returns the stream that just ended
+    poller::refresh(&app).await.unwrap();
+    assert!(!poller::snapshot().unwrap().streamers[0].is_live);
+
+    twitch.set_offline("shokkfamedslayer");
+    poller::refresh(&app).await.unwrap();
+    twitch.set_live("shokkfamedslayer", "Second half", 42);
+    poller::refresh(&app).await.unwrap();
+
+    // Helix is trusted again once it agreed
+    assert!(poller::snapshot().unwrap().streamers[0].is_live);
+}
+
+#[tokio::test]
+async fn ignores_redelivered_messages() {
+    let (_guard, twitch, _app) = setup(&[("Shokk", "shokkfamedslayer")]).await;
+    let (url, app) = start_webhook().await;
+    let user_id = twitch.user_id("shokkfamedslayer").unwrap();
+    twitch.set_live("shokkfamedslayer", "Raid night", 42);
+    poller::refresh(&app).await.unwrap();
+    let offline = message_id();
+    let body = stream_event("stream.offline", &user_id, "shokkfamedslayer");
+    deliver(&url, "notification", &offline, &body, SECRET, TimeDelta::zero()).await;
+    notify(&url, &stream_event("stream.online", &user_id, "shokkfamedslayer")).await;
+
+    let response = deliver(&url, "notification", &offline, &body, SECRET, TimeDelta::zero()).await;
+
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    assert!(poller::snapshot().unwrap().streamers[0].is_live);
+}
+
+#[tokio::test]
+async fn syncs_subscriptions_with_the_roster() {
+    let (_guard, twitch, _app) = setup(&[("Shokk", "shokkfamedslayer"), ("Alpha", "alpha")]).await;
+    let (_url, app) = start_webhook().await;
+    let shokk = twitch.user_id("shokkfamedslayer").unwrap();
+    let alpha = twitch.user_id("alpha").unwrap();
+    twitch.add_subscription("stream.online", &shokk, CALLBACK);
+    // Left the roster
+    twitch.add_subscription("stream.offline", "9999", CALLBACK);
+    // Belongs to another deployment sharing the client ID
+    twitch.add_subscription("stream.offline", "9999", "https://elsewhere.test/eventsub");
+
+    sync_subscriptions(&app).await.unwrap();
+
+    let mut subscriptions = twitch
+        .subscriptions()
+        .iter()
+        .map(|s| {
+            (
+                s["type"].as_str().unwrap().to_string(),
+                s["condition"]["broadcaster_user_id"].as_str().unwrap().to_string(),
+                s["transport"]["callback"].as_str().unwrap().to_string(),
+            )
+        })
+        .collect::<Vec<_>>();
+    subscriptions.sort();
+    let expected = |kind: &str, id: &str, callback: &str| (kind.to_string(), id.to_string(), callback.to_string());
+    let mut expected = vec![
+        expected("stream.online", &shokk, CALLBACK),
+        expected("stream.offline", &shokk, CALLBACK),
+        expected("stream.online", &alpha, CALLBACK),
+        expected("stream.offline", &alpha, CALLBACK),
+        expected("stream.offline", "9999", "https://elsewhere.test/eventsub"),
+    ];
+    expected.sort();
+    assert_eq!(subscriptions, expected);
+}
+
+#[tokio::test]
+async fn subscribes_again_when_twitch_revokes_a_subscription() {
+    let (_guard, twitch, _app) = setup(&[("Shokk", "shokkfamedslayer")]).await;
+    let (url, _app) = start_webhook().await;
+    let user_id = twitch.user_id("shokkfamedslayer").unwrap();
+    let mut body = json!({ "subscription": subscription("stream.online", &user_id) });
+    body["subscription"]["status"] = json!("notification_failures_exceeded");
+
+    let response = deliver(&url, "revocation", &message_id(), &body, SECRET, TimeDelta::zero()).await;
+
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    for _ in 0..50 {
+        if twitch.subscriptions().len() == 2 {
+            return;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    panic!("not subscribed again: {:?}", twitch.subscriptions());
+}
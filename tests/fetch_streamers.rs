@@ -0,0 +1,413 @@
+mod common;
+
+use axum::http::StatusCode;
+use chrono::{DateTime, TimeDelta, Utc};
+use common::{app, fail_persistently, setup};
+use leptos::prelude::{provide_context, Owner};
+use std::{
+    os::unix::fs::PermissionsExt,
+    time::{Duration, Instant},
+};
+use webtv::{
+    config::{Config, ConfigError},
+    fetch_streamer_details::fetch_streamer_details,
+    fetch_streamers::{clear_caches, fetch_streamers, UnavailableReason, UnavailableStreamer},
+    poller, rate_limit,
+    twitch_error::TwitchError,
+};
+
+/// Whole seconds from now, as Twitch reports schedule dates
+fn in_hours(hours: i64) -> DateTime<Utc> {
+    DateTime::from_timestamp(Utc::now().timestamp(), 0).unwrap() + TimeDelta::hours(hours)
+}
+
+#[tokio::test]
+async fn maps_twitch_data_to_streamers() {
+    let (_guard, twitch, app) = setup(&[("Shokk", "ShokkFamedSlayer")]).await;
+    twitch.set_live("shokkfamedslayer", "Raid night", 42);
+
+    let response = poller::refresh(&app).await.unwrap();
+
+    assert_eq!(response.base_addr, "webtv.test");
+    assert_eq!(response.streamers.len(), 1);
+    let streamer = &response.streamers[0];
+    assert_eq!(streamer.display_name, "Shokk");
+    assert_eq!(streamer.channel_name, "shokkfamedslayer");
+    assert_eq!(
+        streamer.avatar_url,
+        "https://static-cdn.jtvnw.net/jtv_user_pictures/shokkfamedslayer-profile_image-300x300.png"
+    );
+    assert!(streamer.is_live);
+    assert_eq!(streamer.viewer_count, Some(42));
+    assert_eq!(streamer.stream_title.as_deref(), Some("Raid night"));
+    assert_eq!(streamer.game_name.as_deref(), Some("Just Chatting"));
+    assert_eq!(streamer.started_at.unwrap().to_rfc3339(), "2026-01-01T18:00:00+00:00");
+    assert_eq!(streamer.language.as_deref(), Some("fr"));
+    assert_eq!(streamer.tags, ["Français"]);
+    assert!(!streamer.is_mature);
+    assert!(streamer
+        .thumbnail_url
+        .as_deref()
+        .unwrap()
+        .starts_with("https://static-cdn.jtvnw.net/previews-ttv/live_user_shokkfamedslayer-854x480.jpg?t="));
+    assert_eq!(streamer.offline_image_url, None);
+}
+
+#[tokio::test]
+async fn carries_offline_banners() {
+    let (_guard, twitch, app) = setup(&[("Shokk", "shokkfamedslayer")]).await;
+    let banner = "https://static-cdn.jtvnw.net/jtv_user_pictures/shokkfamedslayer-channel_offline_image-1920x1080.png";
+    twitch.set_offline_image("shokkfamedslayer", banner);
+
+    let response = poller::refresh(&app).await.unwrap();
+
+    let streamer = &response.streamers[0];
+    assert_eq!(streamer.offline_image_url.as_deref(), Some(banner));
+    assert_eq!(streamer.thumbnail_url, None);
+}
+
+#[tokio::test]
+async fn carries_upcoming_scheduled_streams() {
+    let (_guard, twitch, app) = setup(&[("Shokk", "shokkfamedslayer")]).await;
+    twitch.add_schedule_segment("shokkfamedslayer", in_hours(24), "Raid night");
+    twitch.add_schedule_segment("shokkfamedslayer", in_hours(48), "Canceled raid");
+    twitch.cancel_schedule_segment("shokkfamedslayer", "Canceled raid");
+    twitch.add_schedule_segment("shokkfamedslayer", in_hours(96), "Raid during the break");
+    twitch.set_vacation("shokkfamedslayer", in_hours(72), in_hours(120));
+    twitch.add_schedule_segment("shokkfamedslayer", in_hours(144), "Back from the break");
+    // Beyond the coming week
+    twitch.add_schedule_segment("shokkfamedslayer", in_hours(240), "Next month");
+
+    let response = poller::refresh(&app).await.unwrap();
+
+    let schedule = &response.streamers[0].schedule;
+    let titles = schedule.iter().map(|s| s.title.as_str()).collect::<Vec<_>>();
+    assert_eq!(titles, ["Raid night", "Back from the break"]);
+    assert_eq!(schedule[0].start_time, in_hours(24));
+    assert_eq!(schedule[0].end_time, Some(in_hours(26)));
+    assert_eq!(schedule[0].category.as_deref(), Some("Just Chatting"));
+}
+
+#[tokio::test]
+async fn remembers_channels_without_a_schedule() {
+    let (_guard, twitch, app) = setup(&[("Shokk", "shokkfamedslayer")]).await;
+
+    poller::refresh(&app).await.unwrap();
+    let response = poller::refresh(&app).await.unwrap();
+
+    assert!(response.streamers[0].schedule.is_empty());
+    assert_eq!(twitch.requests("schedule"), 1);
+}
+
+#[tokio::test]
+async fn polls_without_the_schedules_twitch_fails_to_return() {
+    let (_guard, twitch, app) = setup(&[("Shokk", "shokkfamedslayer")]).await;
+    twitch.add_schedule_segment("shokkfamedslayer", in_hours(24), "Raid night");
+    fail_persistently(&twitch, "schedule", StatusCode::SERVICE_UNAVAILABLE);
+
+    let response = poller::refresh(&app).await.unwrap();
+    assert!(response.streamers[0].schedule.is_empty());
+
+    // Requested again by the next poll
+    let response = poller::refresh(&app).await.unwrap();
+    assert_eq!(response.streamers[0].schedule.len(), 1);
+}
+
+#[tokio::test]
+async fn loads_streamer_pages() {
+    let (_guard, twitch, app) = setup(&[("Shokk", "ShokkFamedSlayer")]).await;
+    twitch.set_live("shokkfamedslayer", "Raid night", 42);
+    poller::refresh(&app).await.unwrap();
+    // Server functions find the app services in the reactive context, which lives as long as its owner
+    let owner = Owner::new();
+    owner.set();
+    provide_context(app);
+
+    let details = fetch_streamer_details("ShokkFamedSlayer".to_string())
+        .await
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(details.base_addr, "webtv.test");
+    assert_eq!(details.streamer.display_name, "Shokk");
+    assert!(details.streamer.is_live);
+    assert_eq!(details.description, "Bio of shokkfamedslayer");
+    assert_eq!(fetch_streamer_details("nobody".to_string()).await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn sorts_live_streamers_by_viewers_then_by_name() {
+    let (_guard, twitch, app) = setup(&[
+        ("zed", "zed_offline"),
+        ("Small", "small_live"),
+        ("alpha", "alpha_offline"),
+        ("Big", "big_live"),
+    ])
+    .await;
+    twitch.set_live("small_live", "Small stream", 3);
+    twitch.set_live("big_live", "Big stream", 300);
+
+    let response = poller::refresh(&app).await.unwrap();
+
+    let order = response
+        .streamers
+        .iter()
+        .map(|s| s.display_name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(order, ["Big", "Small", "alpha", "zed"]);
+    assert!(!response.streamers[2].is_live);
+    assert_eq!(response.streamers[2].viewer_count, None);
+    assert_eq!(response.streamers[2].stream_title, None);
+}
+
+#[tokio::test]
+async fn drops_logins_unknown_to_twitch() {
+    let (_guard, twitch, app) = setup(&[("Known", "known"), ("Typo", "knwon")]).await;
+    twitch.remove_user("knwon");
+
+    let response = poller::refresh(&app).await.unwrap();
+
+    assert_eq!(response.streamers.len(), 1);
+    assert_eq!(response.streamers[0].display_name, "Known");
+    assert_eq!(
+        response.unavailable,
+        [UnavailableStreamer {
+            display_name: "Typo".to_string(),
+            login: "knwon".to_string(),
+            reason: UnavailableReason::UnknownLogin,
+        }]
+    );
+}
+
+#[tokio::test]
+async fn reports_accounts_gone_since_the_last_poll() {
+    let (_guard, twitch, app) = setup(&[("Known", "known"), ("Banned", "banned")]).await;
+    poller::refresh(&app).await.unwrap();
+    twitch.remove_user("banned");
+    clear_caches().await;
+
+    let response = poller::refresh(&app).await.unwrap();
+
+    assert_eq!(response.streamers.len(), 1);
+    assert_eq!(response.unavailable.len(), 1);
+    assert_eq!(response.unavailable[0].login, "banned");
+    assert_eq!(response.unavailable[0].reason, UnavailableReason::AccountGone);
+}
+
+#[tokio::test]
+async fn follows_streamers_who_rename_their_account() {
+    let (_guard, twitch, app) = setup(&[("Shokk", "shokkfamedslayer")]).await;
+    poller::refresh(&app).await.unwrap();
+    twitch.rename_user("shokkfamedslayer", "shokk");
+    twitch.set_live("shokk", "Raid night", 42);
+    clear_caches().await;
+
+    let response = poller::refresh(&app).await.unwrap();
+
+    assert_eq!(response.streamers.len(), 1);
+    let streamer = &response.streamers[0];
+    assert_eq!(streamer.display_name, "Shokk");
+    assert_eq!(streamer.channel_name, "shokk");
+    assert!(streamer.is_live);
+}
+
+#[tokio::test]
+async fn batches_logins_and_follows_pagination() {
+    let logins = (0..150).map(|i| format!("streamer_{i}")).collect::<Vec<_>>();
+    let streamers = logins.iter().map(|l| (l.as_str(), l.as_str())).collect::<Vec<_>>();
+    let (_guard, twitch, app) = setup(&streamers).await;
+    for login in &logins {
+        twitch.set_live(login, "Live", 1);
+    }
+    twitch.set_page_size(40);
+
+    let response = poller::refresh(&app).await.unwrap();
+
+    assert_eq!(response.streamers.len(), 150);
+    assert!(response.streamers.iter().all(|s| s.is_live));
+    // One request per batch of 100 for users, and streams pages of 40 per batch
+    assert_eq!(twitch.requests("users"), 2);
+    assert_eq!(twitch.requests("streams"), 3 + 2);
+}
+
+#[tokio::test]
+async fn reuses_cached_users_between_polls() {
+    let (_guard, twitch, app) = setup(&[("Shokk", "shokkfamedslayer")]).await;
+
+    poller::refresh(&app).await.unwrap();
+    poller::refresh(&app).await.unwrap();
+
+    assert_eq!(twitch.requests("users"), 1);
+    assert_eq!(twitch.requests("streams"), 2);
+    assert_eq!(twitch.requests("schedule"), 1);
+}
+
+#[tokio::test]
+async fn serves_the_latest_snapshot_without_querying_twitch() {
+    let (_guard, twitch, app) = setup(&[("Shokk", "shokkfamedslayer")]).await;
+    poller::refresh(&app).await.unwrap();
+    twitch.set_live("shokkfamedslayer", "Raid night", 42);
+
+    let response = fetch_streamers().await.unwrap();
+
+    assert!(!response.streamers[0].is_live);
+    assert_eq!(twitch.requests("streams"), 1);
+}
+
+#[tokio::test]
+async fn fails_when_twitch_fails() {
+    let (_guard, twitch, app) = setup(&[("Shokk", "shokkfamedslayer")]).await;
+    fail_persistently(&twitch, "streams", StatusCode::SERVICE_UNAVAILABLE);
+
+    assert!(matches!(poller::refresh(&app).await, Err(TwitchError::Unavailable(_))));
+}
+
+#[tokio::test]
+async fn serves_stale_data_while_twitch_fails() {
+    let (_guard, twitch, app) = setup(&[("Shokk", "shokkfamedslayer")]).await;
+    twitch.set_live("shokkfamedslayer", "Raid night", 42);
+    let fresh = poller::refresh(&app).await.unwrap();
+    fail_persistently(&twitch, "streams", StatusCode::SERVICE_UNAVAILABLE);
+
+    assert!(poller::refresh(&app).await.is_err());
+    let stale = fetch_streamers().await.unwrap();
+    assert!(stale.stale);
+    assert_eq!(stale.fetched_at, fresh.fetched_at);
+    assert_eq!(stale.streamers, fresh.streamers);
+
+    poller::refresh(&app).await.unwrap();
+    let recovered = fetch_streamers().await.unwrap();
+    assert!(!recovered.stale);
+    assert!(recovered.fetched_at > fresh.fetched_at);
+}
+
+#[tokio::test]
+async fn retries_server_errors() {
+    let (_guard, twitch, app) = setup(&[("Shokk", "shokkfamedslayer")]).await;
+    twitch.fail_next("streams", StatusCode::SERVICE_UNAVAILABLE);
+    twitch.fail_next("streams", StatusCode::BAD_GATEWAY);
+
+    assert!(poller::refresh(&app).await.is_ok());
+    assert_eq!(twitch.requests("streams"), 3);
+}
+
+#[tokio::test]
+async fn tracks_the_rate_limit_bucket() {
+    let (_guard, twitch, app) = setup(&[("Shokk", "shokkfamedslayer")]).await;
+    twitch.drain_rate_limit(500, Duration::from_secs(90));
+
+    poller::refresh(&app).await.unwrap();
+
+    let bucket = rate_limit::bucket().unwrap();
+    assert_eq!(bucket.limit, 800);
+    // One point each for users, streams and the schedule
+    assert_eq!(bucket.remaining, 497);
+}
+
+#[tokio::test]
+async fn waits_for_the_bucket_to_refill() {
+    let (_guard, twitch, app) = setup(&[("Shokk", "shokkfamedslayer")]).await;
+    twitch.drain_rate_limit(2, Duration::from_secs(2));
+
+    let start = Instant::now();
+    poller::refresh(&app).await.unwrap();
+
+    assert!(start.elapsed() >= Duration::from_millis(500));
+    // Users were fetched before the wait, streams and the schedule from the refilled bucket
+    assert_eq!(rate_limit::bucket().unwrap().remaining, 798);
+}
+
+#[tokio::test]
+async fn tells_rejected_credentials_from_rate_limits() {
+    let (_guard, twitch, app) = setup(&[("Shokk", "shokkfamedslayer")]).await;
+    // Rejected again after renewing the token
+    twitch.fail_next("streams", StatusCode::UNAUTHORIZED);
+    twitch.fail_next("streams", StatusCode::UNAUTHORIZED);
+    fail_persistently(&twitch, "streams", StatusCode::TOO_MANY_REQUESTS);
+
+    assert!(matches!(poller::refresh(&app).await, Err(TwitchError::AuthRejected(_))));
+    assert!(matches!(poller::refresh(&app).await, Err(TwitchError::RateLimited(_))));
+}
+
+#[tokio::test]
+async fn renews_revoked_access_tokens() {
+    let (_guard, twitch, app) = setup(&[("Shokk", "shokkfamedslayer")]).await;
+    poller::refresh(&app).await.unwrap();
+    let token_requests = twitch.requests("token");
+    twitch.revoke_tokens();
+
+    poller::refresh(&app).await.unwrap();
+
+    assert_eq!(twitch.requests("token"), token_requests + 1);
+    // Rejected once, then sent again with the new token
+    assert_eq!(twitch.requests("streams"), 3);
+}
+
+#[tokio::test]
+async fn forgets_access_tokens_failing_validation() {
+    let (_guard, twitch, app) = setup(&[("Shokk", "shokkfamedslayer")]).await;
+    poller::refresh(&app).await.unwrap();
+    let token_requests = twitch.requests("token");
+    twitch.revoke_tokens();
+
+    app.twitch.validate_access_token().await.unwrap();
+    poller::refresh(&app).await.unwrap();
+
+    assert_eq!(twitch.requests("validate"), 1);
+    assert_eq!(twitch.requests("token"), token_requests + 1);
+    assert_eq!(twitch.requests("streams"), 2);
+}
+
+#[tokio::test]
+async fn reuses_the_access_token_saved_to_disk() {
+    let (_guard, twitch, _app) = setup(&[("Shokk", "shokkfamedslayer")]).await;
+    let token_path = std::env::temp_dir().join(format!("webtv-token-{}.json", std::process::id()));
+    let _ = std::fs::remove_file(&token_path);
+    // SAFETY: the setup lock is held
+    unsafe { std::env::set_var("TWITCH_TOKEN_PATH", &token_path) };
+    poller::refresh(&app()).await.unwrap();
+
+    // Restarted
+    poller::refresh(&app()).await.unwrap();
+
+    assert_eq!(twitch.requests("token"), 1);
+    let mode = std::fs::metadata(&token_path).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o600);
+}
+
+#[tokio::test]
+async fn reports_missing_configuration() {
+    let (_guard, _twitch, _app) = setup(&[("Shokk", "shokkfamedslayer")]).await;
+    // SAFETY: the setup lock is held
+    unsafe { std::env::remove_var("TWITCH_CLIENT_ID") };
+
+    assert!(matches!(Config::load(), Err(ConfigError::Missing("TWITCH_CLIENT_ID"))));
+}
+
+#[tokio::test]
+async fn reads_the_twitch_urls_from_the_config_file() {
+    let (_guard, twitch, _app) = setup(&[("Shokk", "shokkfamedslayer")]).await;
+    let config_path = std::env::temp_dir().join(format!("webtv-config-{}.toml", std::process::id()));
+    std::fs::write(
+        &config_path,
+        format!(
+            "twitch_api_url = \"{}\"\ntwitch_auth_url = \"{}\"\n",
+            twitch.api_url(),
+            twitch.auth_url()
+        ),
+    )
+    .unwrap();
+    // SAFETY: the setup lock is held
+    unsafe {
+        std::env::remove_var("TWITCH_API_URL");
+        std::env::remove_var("TWITCH_AUTH_URL");
+        std::env::set_var("CONFIG_PATH", &config_path);
+    }
+
+    let app = app();
+    let response = poller::refresh(&app).await.unwrap();
+
+    assert_eq!(app.config.twitch_api_url, twitch.api_url());
+    assert_eq!(response.streamers.len(), 1);
+    assert_eq!(twitch.requests("users"), 1);
+}
@@ -0,0 +1,73 @@
+mod common;
+
+use common::setup;
+use webtv::{history::History, poller};
+
+#[tokio::test]
+async fn records_stream_sessions() {
+    let (_guard, twitch, app) = setup(&[("Shokk", "shokkfamedslayer"), ("Other", "other")]).await;
+    let user_id = twitch.user_id("shokkfamedslayer").unwrap();
+
+    twitch.set_live("shokkfamedslayer", "Raid night", 42);
+    poller::refresh(&app).await.unwrap();
+    twitch.set_live("shokkfamedslayer", "Raid night", 50);
+    poller::refresh(&app).await.unwrap();
+    twitch.set_live("shokkfamedslayer", "Loot night", 30);
+    let last_live = poller::refresh(&app).await.unwrap();
+
+    let sessions = app.history.recent_sessions(&user_id, 10).await.unwrap();
+    assert_eq!(sessions.len(), 1);
+    let session = &sessions[0];
+    assert_eq!(session.login, "shokkfamedslayer");
+    assert_eq!(session.started_at.to_rfc3339(), "2026-01-01T18:00:00+00:00");
+    assert_eq!(session.ended_at, None);
+    let titles = session.segments.iter().map(|s| s.title.as_str()).collect::<Vec<_>>();
+    assert_eq!(titles, ["Raid night", "Loot night"]);
+    assert_eq!(session.segments[0].game_name.as_deref(), Some("Just Chatting"));
+    assert_eq!(session.peak_viewers, Some(50));
+    assert_eq!(session.average_viewers, Some(41));
+    let samples = app.history.viewer_samples(session.id).await.unwrap();
+    let viewer_counts = samples.iter().map(|s| s.viewer_count).collect::<Vec<_>>();
+    assert_eq!(viewer_counts, [42, 50, 30]);
+
+    twitch.set_offline("shokkfamedslayer");
+    poller::refresh(&app).await.unwrap();
+
+    let sessions = app.history.recent_sessions(&user_id, 10).await.unwrap();
+    assert_eq!(sessions[0].ended_at, Some(last_live.fetched_at));
+    let other_id = twitch.user_id("other").unwrap();
+    assert!(app.history.recent_sessions(&other_id, 10).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn resumes_sessions_twitch_still_reports() {
+    let (_guard, twitch, app) = setup(&[("Shokk", "shokkfamedslayer")]).await;
+    let user_id = twitch.user_id("shokkfamedslayer").unwrap();
+
+    twitch.set_live("shokkfamedslayer", "Raid night", 42);
+    poller::refresh(&app).await.unwrap();
+    // A poll missing the stream, as when Twitch lags behind
+    twitch.set_offline("shokkfamedslayer");
+    poller::refresh(&app).await.unwrap();
+    twitch.set_live("shokkfamedslayer", "Raid night", 42);
+    poller::refresh(&app).await.unwrap();
+
+    let sessions = app.history.recent_sessions(&user_id, 10).await.unwrap();
+    assert_eq!(sessions.len(), 1);
+    assert_eq!(sessions[0].ended_at, None);
+    assert_eq!(sessions[0].segments.len(), 1);
+}
+
+#[tokio::test]
+async fn keeps_the_history_across_restarts() {
+    let (_guard, twitch, app) = setup(&[("Shokk", "shokkfamedslayer")]).await;
+    let user_id = twitch.user_id("shokkfamedslayer").unwrap();
+    twitch.set_live("shokkfamedslayer", "Raid night", 42);
+    poller::refresh(&app).await.unwrap();
+
+    let reopened = History::open(&app.config.history_db_path).unwrap();
+
+    let sessions = reopened.recent_sessions(&user_id, 10).await.unwrap();
+    assert_eq!(sessions.len(), 1);
+    assert_eq!(sessions[0].peak_viewers, Some(42));
+}
@@ -0,0 +1,172 @@
+mod common;
+
+use axum::{
+    http::{
+        header::{CACHE_CONTROL, CONTENT_TYPE, ETAG, IF_NONE_MATCH},
+        StatusCode,
+    },
+    routing::get,
+    Router,
+};
+use common::{serve, setup};
+use std::time::{Duration, SystemTime};
+use webtv::{
+    images::{image, image_url, ImageSize, IMAGE_ROUTE},
+    state::AppState,
+};
+
+const AVATAR: &str = "https://static-cdn.jtvnw.net/jtv_user_pictures/shokk-profile_image-300x300.png";
+const PREVIEW: &str = "https://static-cdn.jtvnw.net/previews-ttv/live_user_shokk-854x480.jpg";
+
+/// Starts a server proxying the images, returning its URL
+async fn start_proxy(app: AppState) -> String {
+    serve(Router::new().route(IMAGE_ROUTE, get(image).with_state(app))).await
+}
+
+async fn get_image(proxy: &str, twitch_url: &str, size: ImageSize) -> reqwest::Response {
+    reqwest::get(format!("{proxy}{}", image_url(twitch_url, size)))
+        .await
+        .unwrap()
+}
+
+/// Makes every cached image look older than the cache lifetimes
+fn age_cache(app: &AppState) {
+    for entry in std::fs::read_dir(&app.config.image_cache_dir).unwrap() {
+        let file = std::fs::File::options()
+            .write(true)
+            .open(entry.unwrap().path())
+            .unwrap();
+        file.set_modified(SystemTime::now() - Duration::from_secs(2 * 86400))
+            .unwrap();
+    }
+}
+
+#[test]
+fn points_twitch_images_to_the_proxy() {
+    assert_eq!(
+        image_url(AVATAR, ImageSize::SmallAvatar),
+        "/images/70x70/jtv_user_pictures/shokk-profile_image-300x300.png"
+    );
+    assert_eq!(
+        image_url(&format!("{PREVIEW}?t=42"), ImageSize::Preview),
+        "/images/854x480/previews-ttv/live_user_shokk-854x480.jpg?t=42"
+    );
+    assert_eq!(
+        image_url("https://example.com/avatar.png", ImageSize::Avatar),
+        "https://example.com/avatar.png"
+    );
+    assert_eq!(
+        image_url(
+            "https://static-cdn.jtvnw.net/ttv-boxart/509658-285x380.jpg",
+            ImageSize::Original
+        ),
+        "https://static-cdn.jtvnw.net/ttv-boxart/509658-285x380.jpg"
+    );
+}
+
+#[tokio::test]
+async fn serves_images_in_the_requested_size() {
+    let (_guard, twitch, app) = setup(&[("Shokk", "shokk")]).await;
+    let proxy = start_proxy(app).await;
+
+    let response = get_image(&proxy, AVATAR, ImageSize::SmallAvatar).await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()[CONTENT_TYPE], "image/png");
+    assert_eq!(response.headers()[CACHE_CONTROL], "public, max-age=86400");
+    assert!(response.headers().contains_key(ETAG));
+    assert_eq!(
+        response.text().await.unwrap(),
+        "image jtv_user_pictures/shokk-profile_image-70x70.png"
+    );
+    assert_eq!(
+        twitch.image_requests(),
+        ["jtv_user_pictures/shokk-profile_image-70x70.png"]
+    );
+}
+
+#[tokio::test]
+async fn caches_images_on_disk() {
+    let (_guard, twitch, app) = setup(&[("Shokk", "shokk")]).await;
+    let proxy = start_proxy(app.clone()).await;
+
+    let first = get_image(&proxy, AVATAR, ImageSize::Avatar).await.text().await.unwrap();
+    let second = get_image(&proxy, AVATAR, ImageSize::Avatar).await.text().await.unwrap();
+    assert_eq!(first, second);
+    assert_eq!(twitch.requests("images"), 1);
+
+    // Previews are kept for a few minutes only
+    get_image(&proxy, PREVIEW, ImageSize::Preview).await;
+    age_cache(&app);
+    let response = get_image(&proxy, PREVIEW, ImageSize::Preview).await;
+    assert_eq!(response.headers()[CACHE_CONTROL], "public, max-age=300");
+    assert_eq!(twitch.requests("images"), 3);
+}
+
+#[tokio::test]
+async fn answers_not_modified_to_known_etags() {
+    let (_guard, _twitch, app) = setup(&[("Shokk", "shokk")]).await;
+    let proxy = start_proxy(app).await;
+    let url = format!("{proxy}{}", image_url(AVATAR, ImageSize::Avatar));
+
+    let response = reqwest::get(&url).await.unwrap();
+    let etag = response.headers()[ETAG].clone();
+
+    let response = reqwest::Client::new()
+        .get(&url)
+        .header(IF_NONE_MATCH, etag.clone())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+    assert_eq!(response.headers()[ETAG], etag);
+
+    let response = reqwest::Client::new()
+        .get(&url)
+        .header(IF_NONE_MATCH, "\"outdated\"")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn serves_outdated_images_when_the_cdn_fails() {
+    let (_guard, twitch, app) = setup(&[("Shokk", "shokk")]).await;
+    let proxy = start_proxy(app.clone()).await;
+    let cached = get_image(&proxy, PREVIEW, ImageSize::Preview)
+        .await
+        .text()
+        .await
+        .unwrap();
+    age_cache(&app);
+
+    twitch.fail_next("images", StatusCode::SERVICE_UNAVAILABLE);
+    let response = get_image(&proxy, PREVIEW, ImageSize::Preview).await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.text().await.unwrap(), cached);
+
+    twitch.fail_next("images", StatusCode::SERVICE_UNAVAILABLE);
+    let response = get_image(&proxy, AVATAR, ImageSize::Avatar).await;
+    assert_eq!(response.status(), StatusCode::BAD_GATEWAY);
+}
+
+#[tokio::test]
+async fn only_proxies_twitch_images() {
+    let (_guard, twitch, app) = setup(&[("Shokk", "shokk")]).await;
+    let proxy = start_proxy(app).await;
+
+    for path in [
+        "/images/70x70/jtv_user_pictures/shokk-profile_image-300x300.html",
+        "/images/1x1/jtv_user_pictures/shokk-profile_image-300x300.png",
+        "/images/70x70/jtv_user_pictures/shokk-profile_image.png",
+        // Outside of the directories the pages show
+        "/images/original/ttv-boxart/509658-285x380.jpg",
+        "/images/original/jtv_user_pictures/%2e%2e/ttv-boxart/509658-285x380.jpg",
+    ] {
+        let response = reqwest::get(format!("{proxy}{path}")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND, "{path}");
+    }
+    assert_eq!(twitch.requests("images"), 0);
+}
@@ -0,0 +1,39 @@
+mod common;
+
+use axum::{http::header::CONTENT_TYPE, routing::get, Router};
+use common::{serve, setup};
+use std::time::Duration;
+use webtv::{
+    live_updates::{streamer_events, STREAMER_EVENTS_PATH},
+    poller,
+};
+
+/// Reads the event stream until it contains `needle`, returning what was read
+async fn read_until(response: &mut reqwest::Response, needle: &str) -> String {
+    let mut received = String::new();
+    let read = async {
+        while !received.contains(needle) {
+            let chunk = response.chunk().await.unwrap().expect("event stream is still open");
+            received.push_str(&String::from_utf8_lossy(&chunk));
+        }
+    };
+
+    tokio::time::timeout(Duration::from_secs(5), read)
+        .await
+        .unwrap_or_else(|_| panic!("no {needle:?} in {received:?}"));
+    received
+}
+
+#[tokio::test]
+async fn pushes_snapshots_to_subscribers() {
+    let (_guard, twitch, app) = setup(&[("Shokk", "shokkfamedslayer")]).await;
+    let server = serve(Router::new().route(STREAMER_EVENTS_PATH, get(streamer_events))).await;
+    let mut response = reqwest::get(format!("{server}{STREAMER_EVENTS_PATH}")).await.unwrap();
+    assert_eq!(response.headers()[CONTENT_TYPE], "text/event-stream");
+
+    twitch.set_live("shokkfamedslayer", "Raid night", 42);
+    poller::refresh(&app).await.unwrap();
+
+    let received = read_until(&mut response, "Raid night").await;
+    assert!(received.contains("event: streamers"), "{received}");
+}
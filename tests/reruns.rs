@@ -0,0 +1,85 @@
+mod common;
+
+use axum::http::StatusCode;
+use chrono::{DateTime, TimeDelta, Utc};
+use common::{fail_persistently, setup};
+use std::time::Duration;
+use webtv::{
+    poller,
+    reruns::{load_reruns, RerunKind},
+};
+
+/// Whole seconds before now, as Twitch reports video and clip dates
+fn hours_ago(hours: i64) -> DateTime<Utc> {
+    DateTime::from_timestamp(Utc::now().timestamp(), 0).unwrap() - TimeDelta::hours(hours)
+}
+
+#[tokio::test]
+async fn plays_the_latest_broadcasts_and_top_clips() {
+    let (_guard, twitch, app) = setup(&[("Shokk", "shokk"), ("Other", "other")]).await;
+    twitch.add_video("shokk", "archive", "Old raid night", "2h", hours_ago(72));
+    twitch.add_video("shokk", "archive", "Raid night", "3h2m1s", hours_ago(20));
+    twitch.add_video("shokk", "highlight", "Best of", "5m", hours_ago(2));
+    twitch.add_video("other", "archive", "Chill", "45m30s", hours_ago(30));
+    twitch.add_clip("shokk", "Ace", 50, hours_ago(10));
+    twitch.add_clip("shokk", "Fail", 10, hours_ago(11));
+    twitch.add_clip("shokk", "Clutch", 30, hours_ago(12));
+    twitch.add_clip("shokk", "Oops", 5, hours_ago(13));
+    twitch.add_clip("other", "Forgotten", 1000, hours_ago(24 * 40));
+    let response = poller::refresh(&app).await.unwrap();
+
+    let reruns = load_reruns(&app.twitch, &response.streamers).await;
+
+    // The most recent first, without highlights, older broadcasts, less viewed or old clips
+    let titles = reruns.iter().map(|r| r.title.as_str()).collect::<Vec<_>>();
+    assert_eq!(titles, ["Ace", "Fail", "Clutch", "Raid night", "Chill"]);
+    let raid_night = &reruns[3];
+    assert_eq!(raid_night.kind, RerunKind::Video);
+    assert_eq!(raid_night.display_name, "Shokk");
+    assert_eq!(raid_night.duration, Duration::from_secs(3 * 3600 + 2 * 60 + 1));
+    assert_eq!(raid_night.play_time(), Duration::from_secs(3600));
+    assert_eq!(
+        raid_night.embed_url("webtv.test"),
+        format!(
+            "https://player.twitch.tv/?video={}&parent=webtv.test&autoplay=true",
+            raid_night.id
+        )
+    );
+    assert_eq!(reruns[4].duration, Duration::from_secs(45 * 60 + 30));
+    let ace = &reruns[0];
+    assert_eq!(ace.kind, RerunKind::Clip);
+    assert_eq!(ace.channel_name, "shokk");
+    assert_eq!(ace.duration, Duration::from_secs(30));
+    assert!(ace
+        .embed_url("webtv.test")
+        .starts_with("https://clips.twitch.tv/embed?clip="));
+}
+
+#[tokio::test]
+async fn reuses_cached_reruns_between_page_views() {
+    let (_guard, twitch, app) = setup(&[("Shokk", "shokk")]).await;
+    twitch.add_video("shokk", "archive", "Raid night", "3h", hours_ago(20));
+    let response = poller::refresh(&app).await.unwrap();
+
+    load_reruns(&app.twitch, &response.streamers).await;
+    let reruns = load_reruns(&app.twitch, &response.streamers).await;
+
+    assert_eq!(reruns.len(), 1);
+    assert_eq!(twitch.requests("videos"), 1);
+    assert_eq!(twitch.requests("clips"), 1);
+}
+
+#[tokio::test]
+async fn leaves_out_the_reruns_twitch_fails_to_return() {
+    let (_guard, twitch, app) = setup(&[("Shokk", "shokk")]).await;
+    twitch.add_video("shokk", "archive", "Raid night", "3h", hours_ago(20));
+    twitch.add_clip("shokk", "Ace", 50, hours_ago(10));
+    let response = poller::refresh(&app).await.unwrap();
+    fail_persistently(&twitch, "clips", StatusCode::SERVICE_UNAVAILABLE);
+
+    assert!(load_reruns(&app.twitch, &response.streamers).await.is_empty());
+
+    // Requested again by the next page view
+    let reruns = load_reruns(&app.twitch, &response.streamers).await;
+    assert_eq!(reruns.len(), 2);
+}
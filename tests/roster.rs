@@ -0,0 +1,139 @@
+mod common;
+
+use common::setup;
+use std::{collections::HashMap, time::Duration};
+use webtv::roster::{self, Roster, RosterEntry, RosterError};
+
+fn entry(display_name: &str, login: &str) -> RosterEntry {
+    RosterEntry {
+        display_name: display_name.to_string(),
+        login: login.to_string(),
+        id: None,
+        metadata: HashMap::new(),
+    }
+}
+
+fn with_id(mut entry: RosterEntry, id: &str) -> RosterEntry {
+    entry.id = Some(id.to_string());
+    entry
+}
+
+#[test]
+fn normalizes_logins_to_lowercase() {
+    let roster = Roster::new(vec![entry("Shokk", "ShokkFamedSlayer"), entry("Alpha", "alpha_42")]).unwrap();
+
+    let logins = roster.streamers.iter().map(|s| s.login.as_str()).collect::<Vec<_>>();
+    assert_eq!(logins, ["shokkfamedslayer", "alpha_42"]);
+}
+
+#[test]
+fn rejects_empty_rosters() {
+    assert!(matches!(Roster::new(Vec::new()), Err(RosterError::Empty)));
+}
+
+#[test]
+fn rejects_invalid_logins() {
+    for login in ["", "_shokk", "shokk famed", "shökk", "shokk-famed", &"a".repeat(26)] {
+        let result = Roster::new(vec![entry("Shokk", login)]);
+
+        assert!(
+            matches!(&result, Err(RosterError::InvalidLogin { login: l, .. }) if l == login),
+            "{login:?}: {result:?}"
+        );
+    }
+}
+
+#[test]
+fn explains_the_login_rules() {
+    let error = Roster::new(vec![entry("Shokk", "_shokk")]).unwrap_err();
+
+    assert_eq!(
+        error.to_string(),
+        "invalid Twitch login \"_shokk\" for \"Shokk\" (expected 1 to 25 letters, digits or underscores, not starting \
+         with an underscore)"
+    );
+}
+
+#[test]
+fn rejects_invalid_user_ids() {
+    for id in ["", "12a", "-1"] {
+        let result = Roster::new(vec![with_id(entry("Shokk", "shokk"), id)]);
+
+        assert!(
+            matches!(&result, Err(RosterError::InvalidUserId { id: i, .. }) if i == id),
+            "{id:?}: {result:?}"
+        );
+    }
+}
+
+#[test]
+fn rejects_duplicate_logins() {
+    let result = Roster::new(vec![entry("Shokk", "shokk"), entry("Shokk again", "SHOKK")]);
+
+    assert!(matches!(result, Err(RosterError::DuplicateLogin(login)) if login == "shokk"));
+}
+
+#[test]
+fn rejects_duplicate_user_ids() {
+    let result = Roster::new(vec![
+        with_id(entry("Shokk", "shokk"), "42"),
+        with_id(entry("Shokk", "shokk_renamed"), "42"),
+    ]);
+
+    assert!(matches!(result, Err(RosterError::DuplicateUserId(id)) if id == "42"));
+}
+
+/// Logins of the active roster
+fn active_logins() -> Vec<String> {
+    roster::get()
+        .unwrap()
+        .streamers
+        .iter()
+        .map(|s| s.login.clone())
+        .collect()
+}
+
+#[tokio::test]
+async fn reloads_the_roster_file() {
+    let (_guard, _twitch, app) = setup(&[("Shokk", "shokk")]).await;
+    std::fs::write(
+        &app.config.roster_path,
+        "[[streamers]]\ndisplay_name = \"Shokk\"\nlogin = \"shokk\"\n\n\
+         [[streamers]]\ndisplay_name = \"Alpha\"\nlogin = \"Alpha\"\n",
+    )
+    .unwrap();
+
+    let reloaded = roster::reload(&app).await.unwrap();
+
+    assert_eq!(reloaded.streamers.len(), 2);
+    assert_eq!(active_logins(), ["shokk", "alpha"]);
+}
+
+#[tokio::test]
+async fn keeps_the_previous_roster_when_the_file_is_invalid() {
+    let (_guard, _twitch, app) = setup(&[("Shokk", "shokk")]).await;
+    std::fs::write(&app.config.roster_path, "[[streamers]]\ndisplay_name = \"Alpha\"\n").unwrap();
+
+    assert!(matches!(roster::reload(&app).await, Err(RosterError::Parse(..))));
+    assert_eq!(active_logins(), ["shokk"]);
+}
+
+#[tokio::test]
+async fn watches_the_roster_file() {
+    let (_guard, _twitch, app) = setup(&[("Shokk", "shokk")]).await;
+    roster::spawn_watcher(app.clone());
+    std::fs::write(
+        &app.config.roster_path,
+        "[[streamers]]\ndisplay_name = \"Alpha\"\nlogin = \"alpha\"\n",
+    )
+    .unwrap();
+
+    // The file is checked every 5 seconds
+    for _ in 0..100 {
+        if active_logins() == ["alpha"] {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("roster not reloaded: {:?}", active_logins());
+}